@@ -17,8 +17,17 @@
 extern crate exonum;
 extern crate exonum_time;
 extern crate exonum_configuration;
+extern crate serde_json;
 extern crate lib;
 
+use std::env;
+use std::fs::File;
+use std::io::Read;
+use std::process;
+use std::str::FromStr;
+
+use exonum::crypto::{Hash, PublicKey};
+use exonum::encoding::serialize::{FromHex, ToHex};
 use exonum::helpers::fabric::NodeBuilder;
 use exonum::blockchain::{GenesisConfig, ValidatorKeys};
 use exonum::node::{Node, NodeApiConfig, NodeConfig};
@@ -26,6 +35,12 @@ use exonum::storage::MemoryDB;
 use exonum_time::{TimeService, TimeServiceFactory};
 use lib::timestamping::{TimestampingService, TimestampingServiceFactory};
 use lib::blockchain::{BlockchainService, BlockchainServiceFactory};
+use lib::blockchain::events::{Subscribers, DEFAULT_MAX_SUBSCRIBERS};
+use lib::timestamping::proof::TimestampProof;
+use lib::timestamping::merkle::MerklePath;
+use lib::timestamping::digest::HashAlgorithm;
+use lib::verification::{verify_batch_timestamp_proof, verify_timestamp_proof};
+use lib::verification::ots::{parse_ots, verify_ots_proof};
 
 /// implementation to run `TimestampingService` and `BlockchainService` explorer service for dev-testing
 fn dev_main() {
@@ -67,12 +82,13 @@ fn dev_main() {
 
     exonum::helpers::init_logger().unwrap();
 
+    let subscribers = Subscribers::new();
     let node = Node::new(
         MemoryDB::new(),
         vec![
             Box::new(TimeService::default()),
-            Box::new(TimestampingService::default()),
-            Box::new(BlockchainService::default())
+            Box::new(TimestampingService::new(subscribers.clone())),
+            Box::new(BlockchainService::new(subscribers, DEFAULT_MAX_SUBSCRIBERS))
         ],
         node_config(),
     );
@@ -82,14 +98,132 @@ fn dev_main() {
 
 /// implementation to run `TimestampingService` and `BlockchainService` as service
 fn release() {
+    let subscribers = Subscribers::new();
     NodeBuilder::new()
 //        .with_service(Box::new(exonum_configuration::ServiceFactory))
         .with_service(Box::new(TimeServiceFactory))
-        .with_service(Box::new(BlockchainServiceFactory))
-        .with_service(Box::new(TimestampingServiceFactory))
+        .with_service(Box::new(BlockchainServiceFactory::new(subscribers.clone())))
+        .with_service(Box::new(TimestampingServiceFactory::new(subscribers)))
         .run();
 }
 
+/// Offline verification of the timestamp proof received from `/v0/timestamp/:data_hash/proof`
+///
+/// Usage:
+///   timestamping_run verify --proof <proof.json> --validator <consensus_key> [--validator ...]
+///                           (--data-hash <hex> | --document <path> [--algorithm <name>])
+///                           [--merkle-path <path.json>]
+///   timestamping_run verify --ots <proof.ots> --validator <consensus_key> [--validator ...]
+///                           [--data-hash <hex> | --document <path> [--algorithm <name>]]
+///
+/// Validators consensus keys must be given in the order of the genesis configuration.
+/// `--algorithm` is the hash algorithm the document was timestamped with, `sha256` by default.
+/// `--data-hash` is the key of the timestamp as returned by the service.
+/// `--merkle-path` is the document inclusion path in the case of Merkle batch timestamp.
+/// `.ots` proof carries the document hash and is verified without the Merkle path.
+fn verify(args: &[String]) {
+    fn fail(reason: &str) -> ! {
+        println!("FAIL: {}", reason);
+        process::exit(1);
+    }
+
+    fn read_file(path: &str) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        File::open(path)
+            .and_then(|mut file| file.read_to_end(&mut bytes))
+            .unwrap_or_else(|e| fail(&format!("Couldn't read `{}`: {}", path, e)));
+        bytes
+    }
+
+    let mut proof_path = None;
+    let mut validators = Vec::new();
+    let mut data_hash = None;
+    let mut document = None;
+    let mut algorithm = HashAlgorithm::default();
+    let mut merkle_path_file = None;
+    let mut ots_path = None;
+
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        let value = args.next().unwrap_or_else(|| fail(&format!("Missing value of `{}`", arg)));
+        match arg.as_str() {
+            "--proof" => proof_path = Some(value.clone()),
+            "--validator" => validators.push(PublicKey::from_hex(value).unwrap_or_else(|_| {
+                fail(&format!("Invalid validator key `{}`", value))
+            })),
+            "--data-hash" => data_hash = Some(Hash::from_hex(value).unwrap_or_else(|_| {
+                fail(&format!("Invalid data hash `{}`", value))
+            })),
+            "--document" => document = Some(read_file(value)),
+            "--algorithm" => algorithm = HashAlgorithm::from_str(value).unwrap_or_else(|e| fail(&e)),
+            "--merkle-path" => merkle_path_file = Some(value.clone()),
+            "--ots" => ots_path = Some(value.clone()),
+            _ => fail(&format!("Unknown argument `{}`", arg)),
+        }
+    }
+
+    let conflicts = [
+        ("--ots", ots_path.is_some(), "--proof", proof_path.is_some()),
+        ("--ots", ots_path.is_some(), "--merkle-path", merkle_path_file.is_some()),
+        ("--data-hash", data_hash.is_some(), "--document", document.is_some()),
+    ];
+    for &(first, first_given, second, second_given) in &conflicts {
+        if first_given && second_given {
+            fail(&format!("`{}` can't be used with `{}`", first, second));
+        }
+    }
+
+    if validators.is_empty() {
+        fail("At least one `--validator` is required");
+    }
+    if let Some(document) = document {
+        data_hash = Some(algorithm.storage_key(&algorithm.digest(&document)));
+    }
+
+    let result = if let Some(ots_path) = ots_path {
+        let proof = parse_ots(&read_file(&ots_path)).unwrap_or_else(|e| fail(&e.to_string()));
+        // `.ots` file carries the document hash itself
+        let data_hash = data_hash.unwrap_or(proof.file_hash);
+        verify_ots_proof(&proof, &validators, &data_hash)
+    } else {
+        let proof_path = proof_path.unwrap_or_else(|| fail("`--proof` or `--ots` is required"));
+        let data_hash =
+            data_hash.unwrap_or_else(|| fail("`--data-hash` or `--document` is required"));
+        let proof: TimestampProof = serde_json::from_slice(&read_file(&proof_path))
+            .unwrap_or_else(|e| fail(&format!("Couldn't parse proof: {}", e)));
+
+        match merkle_path_file {
+            Some(path) => {
+                let merkle_path: MerklePath = serde_json::from_slice(&read_file(&path))
+                    .unwrap_or_else(|e| fail(&format!("Couldn't parse Merkle path: {}", e)));
+                verify_batch_timestamp_proof(&proof, &validators, &data_hash, &merkle_path)
+            }
+            None => verify_timestamp_proof(&proof, &validators, &data_hash),
+        }
+    };
+
+    match result {
+        Ok(timestamp) => {
+            println!(
+                "PASS: {} is timestamped at {} (block {})",
+                timestamp.data_hash().to_hex(),
+                timestamp.datetime().to_rfc3339(),
+                timestamp.height()
+            );
+            if timestamp.is_attested() {
+                println!("Attested by validator {}", timestamp.signer().to_hex());
+            } else {
+                println!("Timestamp is not attested by validators yet");
+            }
+        }
+        Err(e) => fail(&e.to_string()),
+    }
+}
+
 fn main() {
-    release();
+    let args: Vec<String> = env::args().collect();
+    match args.get(1).map(String::as_str) {
+        Some("verify") => verify(&args[2..]),
+        _ => release(),
+    }
 }
@@ -24,6 +24,7 @@ use exonum::helpers::{Height, ValidatorId};
 use exonum::node::ApiSender;
 use exonum_time::TimeService;
 
+use blockchain::events::Subscribers;
 use timestamping::transactions::Tx;
 use timestamping::TimestampingService;
 
@@ -131,7 +132,10 @@ fn execute_timestamping(db: Box<Database>, b: &mut Bencher, tx_count: u64, heigh
     }
     let mut blockchain = create_blockchain(
         db,
-        vec![Box::new(TimestampingService::default()), Box::new(TimeService::default())]
+        vec![
+            Box::new(TimestampingService::new(Subscribers::new())),
+            Box::new(TimeService::default())
+        ]
     );
     for i in 0..height {
         let txs = prepare_txs(&mut blockchain, i, tx_count);
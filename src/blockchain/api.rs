@@ -12,38 +12,106 @@
 // See the License for the specific language governing permissions and
 // limitations under the License.
 
-use exonum::api::Api;
-use exonum::blockchain::Blockchain;
-use exonum::crypto::Hash;
+use std::time::SystemTime;
+
+use exonum::api::{Api, ApiError};
+use exonum::blockchain::{Block, Blockchain, Schema};
+use exonum::crypto::{Hash, PublicKey};
+use exonum::storage::Snapshot;
 use exonum::encoding::serialize::FromHex;
 use exonum::node::ApiSender;
 
 use iron::prelude::*;
 use iron::status::Status;
-use iron::headers::ContentType;
+use iron::headers::{CacheControl, CacheDirective, ContentType};
+use iron::mime::Mime;
+use iron::response::WriteBody;
 use iron::modifiers::Header;
 use router::Router;
-use exonum::explorer::BlockchainExplorer;
+use exonum::explorer::{BlockchainExplorer, TransactionInfo};
+use exonum::messages::Precommit;
 use serde_json;
 use exonum::helpers::Height;
+use chrono::{DateTime, TimeZone, Utc};
+
+use super::events::{EventFilter, Subscribers};
+use pagination::page_limit;
 
 /// `RestApi` instance for `BlockchainService`
 #[derive(Clone)]
 pub struct BlockchainApi {
     channel: ApiSender,
     blockchain: Blockchain,
+    subscribers: Subscribers,
+    max_subscribers: usize,
 }
 
 impl BlockchainApi {
-    /// Constructs a `BlockchainApi` for the given `channel` and `blockchain`.
-    pub fn new(channel: ApiSender, blockchain: Blockchain) -> BlockchainApi {
+    /// Constructs a `BlockchainApi` for the given `channel` and `blockchain`,
+    /// events are received through `subscribers`, at most `max_subscribers` at once.
+    pub fn new(
+        channel: ApiSender,
+        blockchain: Blockchain,
+        subscribers: Subscribers,
+        max_subscribers: usize
+    ) -> BlockchainApi {
         BlockchainApi {
             channel,
             blockchain,
+            subscribers,
+            max_subscribers,
         }
     }
 }
 
+/// Default number of blocks per page
+pub const DEFAULT_BLOCKS_LIMIT: u64 = 100;
+/// Maximum number of blocks per page
+pub const MAX_BLOCKS_LIMIT: u64 = 1000;
+
+/// Block header with its hash and time
+#[derive(Serialize)]
+pub struct BlockHeader {
+    pub hash: Hash,
+    pub block: Block,
+    /// median time of the block precommits, `None` for the genesis block
+    pub time: Option<DateTime<Utc>>,
+}
+
+/// Block content with the transactions embedded instead of their hashes
+#[derive(Serialize)]
+pub struct ExpandedBlockInfo {
+    pub block: Block,
+    pub precommits: Vec<Precommit>,
+    pub txs: Vec<TransactionInfo>,
+}
+
+/// Default number of transaction hashes per pool page
+pub const DEFAULT_POOL_LIMIT: u64 = 100;
+/// Maximum number of transaction hashes per pool page
+pub const MAX_POOL_LIMIT: u64 = 1000;
+
+/// Page of the pending transactions pool, `next` is the hash to continue from if any
+#[derive(Serialize)]
+pub struct PoolPage {
+    pub tx_hashes: Vec<Hash>,
+    pub next: Option<Hash>,
+}
+
+/// Presence of the transaction in the pending transactions pool
+#[derive(Serialize)]
+pub struct PoolTransaction {
+    pub tx_hash: Hash,
+    pub in_pool: bool,
+}
+
+/// Range of block headers, `next` is the height to continue from if any
+#[derive(Serialize)]
+pub struct BlocksRange {
+    pub blocks: Vec<BlockHeader>,
+    pub next: Option<Height>,
+}
+
 impl BlockchainApi {
     /// Provides actual blockchain height
     fn get_blockchain_height(&self, _: &mut Request) -> IronResult<Response> {
@@ -90,7 +158,33 @@ impl BlockchainApi {
     ///            "b79a70e327de70df58e563622f81b70bf8dcb384e51b9f03d8cbd3b541b74db9"
     ///        ]
     ///    }
+    ///
+    /// With `expand=true` query param `txs` contains transactions in the same form
+    /// `get_transaction` provides them, i.e. with decoded `content` and execution `status`.
+    ///
+    ///    GET: /api/services/blockchain/v0/block/104?expand=true
+    ///
+    ///    {
+    ///        "block": { ... },
+    ///        "precommits": [ ... ],
+    ///        "txs": [
+    ///            {
+    ///                "content": {
+    ///                    "body": {
+    ///                        "doc_hash": "6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924b45",
+    ///                        "pub_key": "6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924235"
+    ///                    },
+    ///                    ...
+    ///                },
+    ///                "location": { "block_height": "104", "position_in_block": "0" },
+    ///                "status": { "type": "success" },
+    ///                ...
+    ///            },
+    ///            ...
+    ///        ]
+    ///    }
     fn get_block(&self, req: &mut Request) -> IronResult<Response> {
+        let expand = self.expand_param(req)?;
         let path = req.url.path();
         let block_num = path.last().unwrap();
         let num = u64::from_str_radix(block_num, 10u32).map_err(|e| {
@@ -101,14 +195,300 @@ impl BlockchainApi {
             ))
         })?;;
 
+        self.block_response(Some(Height(num)), expand)
+    }
+
+    /// Provides block content and transactions list by block hash,
+    /// the response is the same as `get_block` one
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/block/hash/691625abe58bcf7b824d53f212761accb481140b4159b210d85839db4e9fbea4
+    fn get_block_by_hash(&self, req: &mut Request) -> IronResult<Response> {
+        let expand = self.expand_param(req)?;
+        let path = req.url.path();
+        let block_hash = path.last().unwrap();
+        let hash = Hash::from_hex(block_hash).map_err(|e| {
+            IronError::new(e, (
+                Status::BadRequest,
+                Header(ContentType::json()),
+                "\"Invalid request param: `hash`\"",
+            ))
+        })?;
+
+        let snapshot = self.blockchain.snapshot();
+        let height = Schema::new(&snapshot).blocks().get(&hash).map(|block| block.height());
+        self.block_response(height, expand)
+    }
+
+    /// Provides content and transactions list of the latest committed block,
+    /// the response is the same as `get_block` one
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/block/latest
+    fn get_latest_block(&self, req: &mut Request) -> IronResult<Response> {
+        let expand = self.expand_param(req)?;
         let explorer = BlockchainExplorer::new(&self.blockchain);
-        if let Some(block) = explorer.block(Height(num)) {
+        self.block_response(Some(explorer.height()), expand)
+    }
+
+    /// Parses `expand` query param, `false` by default
+    fn expand_param(&self, req: &mut Request) -> IronResult<bool> {
+        Ok(self.optional_param(req, "expand")?.unwrap_or(false))
+    }
+
+    /// Common `block` response implementation, transactions are embedded in the case of `expand`
+    fn block_response(&self, height: Option<Height>, expand: bool) -> IronResult<Response> {
+        let explorer = BlockchainExplorer::new(&self.blockchain);
+        let block = match height.and_then(|height| explorer.block(height)) {
+            Some(block) => block,
+            None => {
+                return self.not_found_response(
+                    &serde_json::to_value("Block is not found").unwrap(),
+                )
+            }
+        };
+
+        if expand {
+            let txs = block
+                .txs
+                .iter()
+                .filter_map(|tx_hash| explorer.transaction(tx_hash))
+                .collect();
+            let json = ExpandedBlockInfo {
+                block: block.block,
+                precommits: block.precommits,
+                txs,
+            };
+            self.ok_response(&serde_json::to_value(&json).unwrap())
+        } else {
             self.ok_response(&serde_json::to_value(block).unwrap())
+        }
+    }
+
+    /// Provides headers of blocks from `from` to `to` height in ascending order
+    ///
+    /// Query params:
+    ///  - `from` - height of the first block, 0 by default;
+    ///  - `to` - height of the last block, both inclusive, the latest block by default;
+    ///  - `limit` - positive page size, `DEFAULT_BLOCKS_LIMIT` by default, at most `MAX_BLOCKS_LIMIT`.
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/blocks?from=103&to=110&limit=2
+    ///
+    ///    {
+    ///        "blocks": [
+    ///            {
+    ///                "block": { "height": "103", "tx_count": 0, ... },
+    ///                "hash": "270cac720fa15e3e4f419c5ee6548a827ab63a5f1435cb82e6aef368dd66aac1",
+    ///                "time": "2018-03-31T21:11:02.582035126Z"
+    ///            },
+    ///            {
+    ///                "block": { "height": "104", "tx_count": 2, ... },
+    ///                "hash": "691625abe58bcf7b824d53f212761accb481140b4159b210d85839db4e9fbea4",
+    ///                "time": "2018-03-31T21:11:03.097101219Z"
+    ///            }
+    ///        ],
+    ///        "next": "105"
+    ///    }
+    fn get_blocks(&self, req: &mut Request) -> IronResult<Response> {
+        let from: u64 = self.optional_param(req, "from")?.unwrap_or(0);
+        let to: Option<u64> = self.optional_param(req, "to")?;
+        let limit = self.optional_param(req, "limit")?;
+        let limit = page_limit(limit, DEFAULT_BLOCKS_LIMIT, MAX_BLOCKS_LIMIT)?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        let to = to.map_or(schema.height().0, |to| to.min(schema.height().0));
+
+        let blocks: Vec<BlockHeader> = (from..to.saturating_add(1))
+            .take(limit as usize)
+            .filter_map(|height| block_header(&schema, Height(height)))
+            .collect();
+        let next = from.checked_add(limit).filter(|&next| next <= to).map(Height);
+
+        let json = BlocksRange { blocks, next };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Provides header of the block which time is the closest one to the `time` query param
+    /// (Unix seconds)
+    ///
+    /// Block time is the median time of its precommits, which is the local time of validators,
+    /// so it is non-decreasing in practice, but not guaranteed to be.
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/block/by_time?time=1522530663
+    ///
+    ///    {
+    ///        "block": { "height": "104", "tx_count": 2, ... },
+    ///        "hash": "691625abe58bcf7b824d53f212761accb481140b4159b210d85839db4e9fbea4",
+    ///        "time": "2018-03-31T21:11:03.097101219Z"
+    ///    }
+    fn get_block_by_time(&self, req: &mut Request) -> IronResult<Response> {
+        let time: i64 = match self.optional_param(req, "time")? {
+            Some(time) => time,
+            None => Err(ApiError::BadRequest("Required request param: `time`".into()))?,
+        };
+        let time = match Utc.timestamp_opt(time, 0).single() {
+            Some(time) => time,
+            None => Err(ApiError::BadRequest("Param `time` is out of range".into()))?,
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+
+        // genesis block has no precommits, so the search starts from the first block
+        let (mut low, mut high) = (1, schema.height().0 + 1);
+        if low >= high {
+            return self.not_found_response(&serde_json::to_value("Block is not found").unwrap());
+        }
+        while low < high {
+            let middle = low + (high - low) / 2;
+            if block_time(&schema, Height(middle)).map_or(true, |block_time| block_time < time) {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+
+        let distance = |height: u64| {
+            block_time(&schema, Height(height))
+                .map(|block_time| block_time.signed_duration_since(time).num_milliseconds().abs())
+        };
+        let closest = if low > schema.height().0 {
+            low - 1
+        } else if low > 1 && distance(low - 1) <= distance(low) {
+            low - 1
         } else {
-            self.not_found_response(&serde_json::to_value("Block is not found").unwrap())
+            low
+        };
+
+        match block_header(&schema, Height(closest)) {
+            Some(header) => self.ok_response(&serde_json::to_value(&header).unwrap()),
+            None => self.not_found_response(&serde_json::to_value("Block is not found").unwrap()),
         }
     }
 
+    /// Provides hashes of the transactions waiting in the pool in the order of hash, page by page
+    ///
+    /// Query params:
+    ///  - `from` - hash to start the page from, the `next` cursor of the previous page;
+    ///  - `limit` - positive page size, `DEFAULT_POOL_LIMIT` by default, at most `MAX_POOL_LIMIT`.
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/pool?limit=2
+    ///
+    ///    {
+    ///        "next": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67",
+    ///        "tx_hashes": [
+    ///            "a763d3adba6becdf276757f36542ca1af3dbb3fd9f3f6776015372362faf9f4c",
+    ///            "b79a70e327de70df58e563622f81b70bf8dcb384e51b9f03d8cbd3b541b74db9"
+    ///        ]
+    ///    }
+    fn get_pool(&self, req: &mut Request) -> IronResult<Response> {
+        let from: Option<String> = self.optional_param(req, "from")?;
+        let from = match from {
+            Some(from) => Hash::from_hex(&from)
+                .map_err(|_| ApiError::BadRequest("Invalid request param: `from`".into()))?,
+            None => Hash::zero(),
+        };
+        let limit = self.optional_param(req, "limit")?;
+        let limit = page_limit(limit, DEFAULT_POOL_LIMIT, MAX_POOL_LIMIT)? as usize;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = Schema::new(&snapshot);
+        let pool = schema.transactions_pool();
+
+        let mut hashes = pool.iter_from(&from);
+        let tx_hashes: Vec<Hash> = hashes.by_ref().take(limit).collect();
+        let next = hashes.next();
+
+        let json = PoolPage { tx_hashes, next };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Checks whether the transaction `hash` is waiting in the pool
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/pool/a763d3adba6becdf276757f36542ca1af3dbb3fd9f3f6776015372362faf9f4c
+    ///
+    ///    {
+    ///        "in_pool": true,
+    ///        "tx_hash": "a763d3adba6becdf276757f36542ca1af3dbb3fd9f3f6776015372362faf9f4c"
+    ///    }
+    fn get_pool_transaction(&self, req: &mut Request) -> IronResult<Response> {
+        let path = req.url.path();
+        let tx_hash = path.last().unwrap();
+        let tx_hash = Hash::from_hex(tx_hash).map_err(|e| {
+            IronError::new(e, (
+                Status::BadRequest,
+                Header(ContentType::json()),
+                "\"Invalid request param: `hash`\"",
+            ))
+        })?;
+
+        let snapshot = self.blockchain.snapshot();
+        let in_pool = Schema::new(&snapshot).transactions_pool().contains(&tx_hash);
+
+        let json = PoolTransaction { tx_hash, in_pool };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Subscribes to the committed blocks and timestamps as server-sent events
+    ///
+    /// Query params:
+    ///  - `data_hash` - optional filter of timestamp events by `data_hash`;
+    ///  - `author` - optional filter of timestamp events by `author` key.
+    ///
+    /// Block events are pushed regardless of filters. The stream is kept open
+    /// until the client disconnects, at most `max_subscribers` of the node configuration
+    /// (`DEFAULT_MAX_SUBSCRIBERS` by default) streams are served at once.
+    ///
+    /// # Examples
+    ///    GET: /api/services/blockchain/v0/subscribe?author=6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924235
+    ///
+    ///    event: block
+    ///    data: {"block":{"height":"104","tx_count":2,...},"hash":"691625ab...","time":"2018-03-31T21:11:03.097101219Z"}
+    ///
+    ///    event: timestamp
+    ///    data: {"author":"6ce29b2d...","data_hash":"b900b8e9...","height":"104",...}
+    ///
+    fn get_subscribe(&self, req: &mut Request) -> IronResult<Response> {
+        let data_hash: Option<String> = self.optional_param(req, "data_hash")?;
+        let data_hash = match data_hash {
+            Some(data_hash) => Some(Hash::from_hex(&data_hash)
+                .map_err(|_| ApiError::BadRequest("Invalid request param: `data_hash`".into()))?),
+            None => None,
+        };
+        let author: Option<String> = self.optional_param(req, "author")?;
+        let author = match author {
+            Some(author) => Some(PublicKey::from_hex(&author)
+                .map_err(|_| ApiError::BadRequest("Invalid request param: `author`".into()))?),
+            None => None,
+        };
+
+        let filter = EventFilter { data_hash, author };
+        let stream = match self.subscribers.subscribe(filter, self.max_subscribers) {
+            Some(stream) => stream,
+            None => {
+                return Ok(Response::with((
+                    Status::ServiceUnavailable,
+                    Header(ContentType::json()),
+                    "\"Too many subscriptions\"",
+                )))
+            }
+        };
+
+        let content_type: Mime = "text/event-stream".parse().unwrap();
+        let body: Box<WriteBody> = Box::new(stream);
+        Ok(Response::with((
+            Status::Ok,
+            Header(ContentType(content_type)),
+            Header(CacheControl(vec![CacheDirective::NoCache])),
+            body,
+        )))
+    }
+
     /// Provides transaction content by `tx_hash` number
     ///
     /// # Examples
@@ -161,6 +541,32 @@ impl BlockchainApi {
 
 }
 
+/// Median time of the block precommits at `height`
+fn block_time<T: AsRef<Snapshot>>(schema: &Schema<T>, height: Height) -> Option<DateTime<Utc>> {
+    let block_hash = schema.block_hash_by_height(height)?;
+    let mut times: Vec<SystemTime> = schema
+        .precommits(&block_hash)
+        .iter()
+        .map(|precommit| precommit.time())
+        .collect();
+    if times.is_empty() {
+        return None;
+    }
+    times.sort();
+    Some(DateTime::from(times[times.len() / 2]))
+}
+
+/// Header of the block at `height`
+pub fn block_header<T: AsRef<Snapshot>>(schema: &Schema<T>, height: Height) -> Option<BlockHeader> {
+    let hash = schema.block_hash_by_height(height)?;
+    let block = schema.blocks().get(&hash)?;
+    Some(BlockHeader {
+        hash,
+        block,
+        time: block_time(schema, height),
+    })
+}
+
 /// `Api` trait implementation.
 ///
 /// `Api` facilitates conversion between read requests and REST endpoints;
@@ -172,11 +578,32 @@ impl Api for BlockchainApi {
         let self_ = self.clone();
         let get_block = move |req: &mut Request| self_.get_block(req);
         let self_ = self.clone();
+        let get_block_by_hash = move |req: &mut Request| self_.get_block_by_hash(req);
+        let self_ = self.clone();
+        let get_latest_block = move |req: &mut Request| self_.get_latest_block(req);
+        let self_ = self.clone();
+        let get_blocks = move |req: &mut Request| self_.get_blocks(req);
+        let self_ = self.clone();
+        let get_block_by_time = move |req: &mut Request| self_.get_block_by_time(req);
+        let self_ = self.clone();
+        let get_pool = move |req: &mut Request| self_.get_pool(req);
+        let self_ = self.clone();
+        let get_pool_transaction = move |req: &mut Request| self_.get_pool_transaction(req);
+        let self_ = self.clone();
+        let get_subscribe = move |req: &mut Request| self_.get_subscribe(req);
+        let self_ = self.clone();
         let get_tx = move |req: &mut Request| self_.get_transaction(req);
 
         // Bind handlers to specific routes.
         router.get("/v0/height", get_blockchain_height, "get_blockchain_height");
         router.get("/v0/block/:num", get_block, "get_block");
+        router.get("/v0/block/hash/:hash", get_block_by_hash, "get_block_by_hash");
+        router.get("/v0/block/latest", get_latest_block, "get_latest_block");
+        router.get("/v0/blocks", get_blocks, "get_blocks");
+        router.get("/v0/block/by_time", get_block_by_time, "get_block_by_time");
         router.get("/v0/transaction/:hash", get_tx, "get_tx");
+        router.get("/v0/pool", get_pool, "get_pool");
+        router.get("/v0/pool/:hash", get_pool_transaction, "get_pool_transaction");
+        router.get("/v0/subscribe", get_subscribe, "get_subscribe");
     }
 }
@@ -0,0 +1,242 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{self, Receiver, RecvTimeoutError, Sender};
+use std::time::Duration;
+
+use exonum::crypto::{Hash, PublicKey};
+use iron::response::WriteBody;
+use serde_json;
+
+use timestamping::schema::Timestamp;
+use super::api::BlockHeader;
+
+/// Default maximum number of simultaneous subscriptions
+///
+/// Each subscription holds one of the API server threads while it is open, as the requests
+/// waiting for commit do (see `MAX_WAITERS`). Nodes with more API threads may raise the limit
+/// by `max_subscribers` of the `blockchain` section of the node `services_configs`.
+pub const DEFAULT_MAX_SUBSCRIBERS: usize = 2;
+
+/// Interval of the keep-alive comments sent to the idle subscription
+const KEEP_ALIVE_SECS: u64 = 15;
+
+/// Filter of the `Timestamp` events, block events are never filtered
+#[derive(Clone, Debug, Default)]
+pub struct EventFilter {
+    pub data_hash: Option<Hash>,
+    pub author: Option<PublicKey>,
+}
+
+impl EventFilter {
+    fn matches(&self, timestamp: &Timestamp) -> bool {
+        self.data_hash.map_or(true, |data_hash| data_hash == *timestamp.data_hash())
+            && self.author.map_or(true, |author| author == *timestamp.author())
+    }
+}
+
+/// Event pushed to the subscribers after the block commit
+pub enum Event {
+    /// Header of the committed block
+    Block(BlockHeader),
+    /// `Timestamp` created in the committed block
+    Timestamp(Timestamp),
+}
+
+impl Event {
+    /// Server-sent events frame of the event, the event name is its type.
+    fn frame(&self) -> String {
+        let (name, data) = match *self {
+            Event::Block(ref header) => ("block", serde_json::to_string(header).unwrap()),
+            Event::Timestamp(ref timestamp) => {
+                ("timestamp", serde_json::to_string(timestamp).unwrap())
+            }
+        };
+        format!("event: {}\ndata: {}\n\n", name, data)
+    }
+}
+
+#[derive(Debug)]
+struct Subscriber {
+    filter: EventFilter,
+    sender: Sender<String>,
+}
+
+/// Registry of the event subscriptions
+///
+/// `BlockchainApi` registers subscriptions, `BlockchainService` pushes block events
+/// and `TimestampingService` pushes timestamp events to them, so all of them
+/// must be constructed with the same registry.
+#[derive(Clone, Debug, Default)]
+pub struct Subscribers {
+    inner: Arc<Mutex<Vec<Subscriber>>>,
+}
+
+impl Subscribers {
+    pub fn new() -> Subscribers {
+        Subscribers::default()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.inner.lock().unwrap().is_empty()
+    }
+
+    /// Registers new subscription, `None` in the case of `max_subscribers` is reached.
+    pub fn subscribe(&self, filter: EventFilter, max_subscribers: usize) -> Option<EventStream> {
+        let mut subscribers = self.inner.lock().unwrap();
+        if subscribers.len() >= max_subscribers {
+            return None;
+        }
+
+        let (sender, receiver) = mpsc::channel();
+        subscribers.push(Subscriber { filter, sender });
+        Some(EventStream { receiver })
+    }
+
+    /// Pushes `events` to the matching subscribers, closed subscriptions are dropped.
+    pub fn notify(&self, events: &[Event]) {
+        let frames: Vec<(&Event, String)> = events
+            .iter()
+            .map(|event| (event, event.frame()))
+            .collect();
+
+        let mut subscribers = self.inner.lock().unwrap();
+        subscribers.retain(|subscriber| {
+            frames
+                .iter()
+                .filter(|&&(event, _)| match *event {
+                    Event::Block(_) => true,
+                    Event::Timestamp(ref timestamp) => subscriber.filter.matches(timestamp),
+                })
+                .all(|&(_, ref frame)| subscriber.sender.send(frame.clone()).is_ok())
+        });
+    }
+}
+
+/// `text/event-stream` response body of the subscription
+///
+/// The body is written until the client disconnects.
+pub struct EventStream {
+    receiver: Receiver<String>,
+}
+
+impl WriteBody for EventStream {
+    fn write_body(&mut self, res: &mut Write) -> io::Result<()> {
+        res.write_all(b": subscribed\n\n")?;
+        res.flush()?;
+        loop {
+            match self.receiver.recv_timeout(Duration::from_secs(KEEP_ALIVE_SECS)) {
+                Ok(frame) => res.write_all(frame.as_bytes())?,
+                Err(RecvTimeoutError::Timeout) => res.write_all(b": keep-alive\n\n")?,
+                Err(RecvTimeoutError::Disconnected) => return Ok(()),
+            }
+            res.flush()?;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::Utc;
+    use exonum::crypto::{gen_keypair, hash, Hash, PublicKey};
+    use exonum::helpers::Height;
+    use serde_json;
+
+    use timestamping::digest::HashAlgorithm;
+    use timestamping::schema::Timestamp;
+    use super::*;
+
+    fn timestamp(data_hash: &Hash, author: &PublicKey) -> Timestamp {
+        Timestamp::from_parts(
+            &Utc::now(),
+            HashAlgorithm::Sha256,
+            data_hash.as_ref(),
+            author,
+            Height(1),
+            "",
+            &Hash::zero()
+        )
+    }
+
+    #[test]
+    fn test_filter_matches() {
+        let (author, _) = gen_keypair();
+        let data_hash = hash(b"document");
+        let timestamp = timestamp(&data_hash, &author);
+
+        assert!(EventFilter::default().matches(&timestamp));
+        let by_hash = EventFilter { data_hash: Some(data_hash), author: None };
+        assert!(by_hash.matches(&timestamp));
+        let by_author = EventFilter { data_hash: None, author: Some(author) };
+        assert!(by_author.matches(&timestamp));
+        let by_both = EventFilter { data_hash: Some(data_hash), author: Some(author) };
+        assert!(by_both.matches(&timestamp));
+
+        let other_hash = EventFilter { data_hash: Some(hash(b"other")), author: Some(author) };
+        assert!(!other_hash.matches(&timestamp));
+        let other_author = EventFilter {
+            data_hash: Some(data_hash),
+            author: Some(gen_keypair().0),
+        };
+        assert!(!other_author.matches(&timestamp));
+    }
+
+    #[test]
+    fn test_frame() {
+        let timestamp = timestamp(&hash(b"document"), &gen_keypair().0);
+        let frame = Event::Timestamp(timestamp.clone()).frame();
+
+        assert!(frame.starts_with("event: timestamp\ndata: "));
+        assert!(frame.ends_with("\n\n"));
+        let data = &frame["event: timestamp\ndata: ".len()..frame.len() - 2];
+        assert!(!data.contains('\n'));
+        assert_eq!(
+            serde_json::from_str::<serde_json::Value>(data).unwrap(),
+            serde_json::to_value(&timestamp).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_notify() {
+        let (author, _) = gen_keypair();
+        let data_hash = hash(b"document");
+        let subscribers = Subscribers::new();
+        let all = subscribers.subscribe(EventFilter::default(), 2).unwrap();
+        let other = EventFilter { data_hash: None, author: Some(gen_keypair().0) };
+        let filtered = subscribers.subscribe(other, 2).unwrap();
+        assert!(subscribers.subscribe(EventFilter::default(), 2).is_none());
+
+        let event = Event::Timestamp(timestamp(&data_hash, &author));
+        subscribers.notify(&[event]);
+        assert!(all.receiver.try_recv().unwrap().starts_with("event: timestamp\n"));
+        assert!(filtered.receiver.try_recv().is_err());
+    }
+
+    #[test]
+    fn test_notify_drops_closed_subscriptions() {
+        let subscribers = Subscribers::new();
+        let open = subscribers.subscribe(EventFilter::default(), 2).unwrap();
+        let closed = subscribers.subscribe(EventFilter::default(), 2).unwrap();
+        drop(closed);
+
+        let event = Event::Timestamp(timestamp(&hash(b"document"), &gen_keypair().0));
+        subscribers.notify(&[event]);
+        assert_eq!(subscribers.inner.lock().unwrap().len(), 1);
+        assert!(open.receiver.try_recv().is_ok());
+        assert!(subscribers.subscribe(EventFilter::default(), 2).is_some());
+    }
+}
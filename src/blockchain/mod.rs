@@ -14,33 +14,45 @@
 
 
 pub mod transactions;
+pub mod events;
 mod api;
 
 
 use exonum::api::Api;
-use exonum::blockchain::{Service, ApiContext, Transaction, TransactionSet};
+use exonum::blockchain::{Schema, Service, ServiceContext, ApiContext, Transaction, TransactionSet};
 use exonum::crypto::Hash;
 use exonum::encoding;
-use exonum::helpers::fabric::{ServiceFactory, Context};
+use exonum::helpers::fabric::{keys, ServiceFactory, Context};
 use exonum::messages::RawTransaction;
 use exonum::storage::Snapshot;
 
 
 use iron::Handler;
 use router::Router;
+use serde_json;
 
-use blockchain::api::BlockchainApi;
+use blockchain::api::{block_header, BlockchainApi};
+use blockchain::events::{Event, Subscribers, DEFAULT_MAX_SUBSCRIBERS};
 use blockchain::transactions::BlockchainTransactions;
 
 pub const SERVICE_ID: u16 = 2;
 
-pub struct BlockchainService;
+pub struct BlockchainService {
+    subscribers: Subscribers,
+    max_subscribers: usize,
+}
 
 
 /// `BlockchainService` instance which provides Api to explore blockchain state
 impl BlockchainService {
-    pub fn default() -> BlockchainService {
-        BlockchainService
+    /// Constructs the service serving up to `max_subscribers` event subscriptions of
+    /// `subscribers`, timestamp events are pushed to them by `TimestampingService`
+    /// sharing the same registry.
+    pub fn new(subscribers: Subscribers, max_subscribers: usize) -> BlockchainService {
+        BlockchainService {
+            subscribers,
+            max_subscribers,
+        }
     }
 }
 
@@ -66,10 +78,33 @@ impl Service for BlockchainService {
         vec![]
     }
 
+    /// Pushes the committed block header to the subscribers.
+    ///
+    /// Services are committed in the order of their ids, so the block event precedes
+    /// the timestamp events pushed by `TimestampingService`.
+    fn handle_commit(&self, context: &ServiceContext) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        let schema = Schema::new(context.snapshot());
+        let events: Vec<Event> = block_header(&schema, schema.height())
+            .map(Event::Block)
+            .into_iter()
+            .collect();
+
+        self.subscribers.notify(&events);
+    }
+
     /// Create a REST `Handler` to process web requests to the node.
     fn public_api_handler(&self, ctx: &ApiContext) -> Option<Box<Handler>> {
         let mut router = Router::new();
-        let api = BlockchainApi::new(ctx.node_channel().clone(), ctx.blockchain().clone());
+        let api = BlockchainApi::new(
+            ctx.node_channel().clone(),
+            ctx.blockchain().clone(),
+            self.subscribers.clone(),
+            self.max_subscribers
+        );
         api.wire(&mut router);
         Some(Box::new(router))
     }
@@ -77,12 +112,28 @@ impl Service for BlockchainService {
 
 /// Factory instance for `BlockchainService` creation.
 #[derive(Debug)]
-pub struct BlockchainServiceFactory;
+pub struct BlockchainServiceFactory {
+    subscribers: Subscribers,
+}
 
+impl BlockchainServiceFactory {
+    /// Constructs the factory of `BlockchainService` serving subscriptions of `subscribers`.
+    pub fn new(subscribers: Subscribers) -> BlockchainServiceFactory {
+        BlockchainServiceFactory { subscribers }
+    }
+}
 
 /// Factory implementation
 impl ServiceFactory for BlockchainServiceFactory {
-    fn make_service(&mut self, _: &Context) -> Box<Service> {
-        Box::new(BlockchainService::default())
+    /// Reads `max_subscribers` of the `blockchain` section of the node `services_configs`,
+    /// `DEFAULT_MAX_SUBSCRIBERS` if it's missing.
+    fn make_service(&mut self, context: &Context) -> Box<Service> {
+        let max_subscribers = context
+            .get(keys::NODE_CONFIG)
+            .ok()
+            .and_then(|config| serde_json::to_value(&config.services_configs).ok())
+            .and_then(|configs| configs["blockchain"]["max_subscribers"].as_u64())
+            .map_or(DEFAULT_MAX_SUBSCRIBERS, |max_subscribers| max_subscribers as usize);
+        Box::new(BlockchainService::new(self.subscribers.clone(), max_subscribers))
     }
 }
@@ -17,6 +17,8 @@
 
 #[macro_use]
 extern crate failure;
+#[macro_use]
+extern crate log;
 extern crate serde;
 extern crate serde_json;
 #[macro_use]
@@ -31,6 +33,8 @@ extern crate chrono;
 extern crate base64;
 #[macro_use]
 extern crate base64_serde;
+extern crate sha2;
+extern crate sha3;
 
 extern crate futures;
 extern crate tempdir;
@@ -38,6 +42,8 @@ extern crate test;
 
 pub mod timestamping;
 pub mod blockchain;
+pub mod verification;
+mod pagination;
 #[cfg(test)]
 mod benches;
 
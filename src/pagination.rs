@@ -0,0 +1,42 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+//
+
+use exonum::api::ApiError;
+
+/// Page size of the `limit` request param, `default` if it's missing and at most `max`
+///
+/// Zero `limit` is rejected, as the page would be empty and its `next` cursor would point
+/// to the same position, so clients following the cursors would never stop.
+pub fn page_limit(limit: Option<u64>, default: u64, max: u64) -> Result<u64, ApiError> {
+    match limit {
+        Some(0) => Err(ApiError::BadRequest("Request param `limit` must be positive".into())),
+        Some(limit) => Ok(limit.min(max)),
+        None => Ok(default),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::page_limit;
+
+    #[test]
+    fn test_page_limit() {
+        assert_eq!(page_limit(None, 20, 100).unwrap(), 20);
+        assert_eq!(page_limit(Some(1), 20, 100).unwrap(), 1);
+        assert_eq!(page_limit(Some(100), 20, 100).unwrap(), 100);
+        assert_eq!(page_limit(Some(u64::max_value()), 20, 100).unwrap(), 100);
+        assert!(page_limit(Some(0), 20, 100).is_err());
+    }
+}
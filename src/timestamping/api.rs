@@ -13,36 +13,53 @@
 // limitations under the License.
 
 
+use std::collections::HashSet;
+use std::error::Error;
+use std::io::{ErrorKind, Read};
+use std::time::Duration;
+
 use exonum::blockchain::{ApiContext, Blockchain, Transaction};
 use exonum::encoding::serialize::FromHex;
 use exonum::node::{TransactionSend, ApiSender};
 use exonum::crypto::{Hash, PublicKey, SecretKey, hash};
 use exonum::api::{Api, ApiError};
+use exonum::storage::Snapshot;
 use iron::prelude::*;
 use iron::status::Status;
 use iron::headers::ContentType;
+use iron::mime::{Mime, SubLevel, TopLevel};
 use iron::modifiers::Header;
 use router::Router;
 
 use bodyparser;
 use serde_json;
-use base64::STANDARD;
+use base64::{self, STANDARD};
 
 use super::schema::{Timestamp, TimestampingSchema};
-use super::transactions::Tx;
+use super::transactions::{TimestampingTransactions, Tx, TxBatch, TxDigest, TxNewVersion,
+                          TxWithMetainfo};
+use super::merkle::{MerkleBatch, MerklePath};
+use super::status::{TransactionStatus, Waiters};
+use super::proof::TimestampProof;
+use super::ots::{OtsError, OtsProof};
+use super::digest::{DigestStream, HashAlgorithm};
+use super::errors::Error as TimestampingError;
+use pagination::page_limit;
 
 /// `RestApi` instance for `TimestampingService`
 #[derive(Clone)]
 pub struct TimestampingApi {
     channel: ApiSender,
     blockchain: Blockchain,
-    service_keys: (PublicKey, SecretKey)
+    service_keys: (PublicKey, SecretKey),
+    waiters: Waiters
 }
 
 
 impl TimestampingApi {
-    /// Constructs a `TimestampingApi` for the given `context`.
-    pub fn new(context: &ApiContext) -> TimestampingApi {
+    /// Constructs a `TimestampingApi` for the given `context`,
+    /// `waiters` limit the requests waiting for commit.
+    pub fn new(context: &ApiContext, waiters: Waiters) -> TimestampingApi {
         let channel = context.node_channel().clone();
         let blockchain = context.blockchain().clone();
         let service_keys = (*context.public_key(), context.secret_key().clone());
@@ -50,7 +67,8 @@ impl TimestampingApi {
         TimestampingApi {
             channel,
             blockchain,
-            service_keys
+            service_keys,
+            waiters
         }
     }
 }
@@ -58,17 +76,123 @@ impl TimestampingApi {
 /// POST Request message structure in the case of `data_hash` is provided
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TransactionRequestHash {
-    pub data_hash: Hash
+    pub data_hash: Hash,
+    pub doc_metainfo: Option<String>
 }
 
-/// POST Request message structure in the case of `data` (base64 encoded) is provided
+/// POST Request message structure in the case of `data` (base64 encoded) is provided,
+/// `algorithm` is SHA-256 by default
 base64_serde_type!(Base64Standard, STANDARD);
 #[derive(Serialize, Deserialize, Clone)]
 pub struct TransactionRequestBase64 {
     #[serde(with = "Base64Standard")]
-    pub data: Vec<u8>
+    pub data: Vec<u8>,
+    pub doc_metainfo: Option<String>,
+    pub algorithm: Option<HashAlgorithm>
+}
+
+/// POST Request message structure in the case of `digest` (hex encoded) made with
+/// `algorithm` is provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransactionRequestDigest {
+    pub algorithm: HashAlgorithm,
+    pub digest: String,
+    pub doc_metainfo: Option<String>
+}
+
+
+/// POST Request message structure in the case of new version of the document is provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransactionRequestVersion {
+    pub data_hash: Hash,
+    pub prev_hash: Hash,
+    pub doc_metainfo: Option<String>
+}
+
+/// Maximum number of items in the batch request
+pub const MAX_BATCH_SIZE: usize = 1000;
+
+/// Batch request item, either `data_hash` or `data` (base64 encoded) must be provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BatchItemRequest {
+    pub data_hash: Option<Hash>,
+    pub data: Option<String>
+}
+
+/// Batch response item, `error` is set in the case of the item is rejected
+#[derive(Serialize, Deserialize, Clone)]
+pub struct BatchItemResponse {
+    pub tx_hash: Option<Hash>,
+    pub data_hash: Option<Hash>,
+    pub error: Option<String>
+}
+
+/// POST Request message structure in the case of Merkle batch of `data_hashes` is provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransactionRequestMerkle {
+    pub data_hashes: Vec<Hash>
+}
+
+/// Inclusion path of the document into the Merkle batch
+#[derive(Serialize, Deserialize, Clone)]
+pub struct MerklePathResponse {
+    pub data_hash: Hash,
+    pub path: MerklePath
+}
+
+/// Response message structure in the case of Merkle batch is provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TransactionResponseMerkle {
+    pub tx_hash: Hash,
+    pub root_hash: Hash,
+    pub paths: Vec<MerklePathResponse>
+}
+
+/// Default number of timestamps per page
+pub const DEFAULT_PAGE_LIMIT: u64 = 100;
+/// Maximum number of timestamps per page
+pub const MAX_PAGE_LIMIT: u64 = 1000;
+/// Maximum number of timestamps scanned to fill the page filtered by time
+pub const MAX_SCANNED_ENTRIES: usize = 10_000;
+
+/// Page of timestamps listing, `next` is the cursor of the next page if any
+#[derive(Serialize, Deserialize, Clone)]
+pub struct TimestampsPage<C> {
+    pub timestamps: Vec<Timestamp>,
+    pub next: Option<C>,
+}
+
+/// Versions of the document in the order of creation
+#[derive(Serialize, Deserialize, Clone)]
+pub struct DocumentVersions {
+    pub document_id: Hash,
+    pub versions: Vec<Timestamp>,
 }
 
+/// Size of the chunks the uploaded document is hashed by
+pub const UPLOAD_CHUNK_SIZE: usize = 64 * 1024;
+
+/// Response message structure of the document verification
+#[derive(Serialize, Deserialize)]
+pub struct VerificationResponse {
+    pub exists: bool,
+    pub data_hash: Hash,
+    pub timestamp: Option<Timestamp>,
+    pub proof: Option<TimestampProof>
+}
+
+/// Maximum time in seconds the `wait` request blocks for
+pub const MAX_WAIT_SECS: u64 = 60;
+
+/// Response message structure in the case of `wait` query param is provided
+#[derive(Serialize, Deserialize, Clone)]
+pub struct CommittedTransactionResponse {
+    pub tx_hash: Hash,
+    pub data_hash: Hash,
+    #[serde(flatten)]
+    pub status: TransactionStatus,
+    pub timestamp: Option<Timestamp>
+}
 
 /// Response message structure in the case of `data` (base64 encoded) is provided
 #[derive(Serialize, Deserialize,Clone)]
@@ -85,11 +209,25 @@ impl TimestampingApi {
     /// GET: /api/services/timestamping/v0/timestamp/b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb01
     ///
     /// {
+    ///   "author": "6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924235",
     ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
-    ///   "signature": "3cfd3c72d37183afbdf12b10dc6d605592ea9d4594a03315a631cfbadfd8e0f7",
-    ///   "timestamp": "1522587390"
+    ///   "height": "12",
+    ///   "metainfo": "contract.pdf",
+    ///   "nanos": 97101219,
+    ///   "signature": "2f23e7a4876fa9a891ba25e57d07ea511e920286951d28e82ce008b46d42ffc466c469ad0d8d82c8ec43200e10c87c14409174b4fbce61b6ad99af8d02c8fc0e",
+    ///   "signer": "a763d3adba6becdf276757f36542ca1af3dbb3fd9f3f6776015372362faf9f4c",
+    ///   "timestamp": "1522587390",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
     /// }
     ///
+    /// `signature` is Ed25519 signature of `TimestampPayload` made by the validator
+    /// service key `signer`. Both are zero until the timestamp is attested.
+    ///
+    /// `timestamp` holds Unix seconds and `nanos` the nanoseconds part of the consolidated time.
+    ///
+    /// `height` and `tx_hash` refer to the block and the transaction the timestamp was created by,
+    /// see `/api/services/blockchain/v0/block/:num` and `/api/services/blockchain/v0/transaction/:hash`.
+    ///
     fn get_timestamp(&self, req: &mut Request) -> IronResult<Response> {
         let path = req.url.path();
         let document_hash = path.last().unwrap();
@@ -111,14 +249,421 @@ impl TimestampingApi {
         }
     }
 
-    /// Provides all stored timestamps, debug only method
-    fn get_timestamps(&self, _: &mut Request) -> IronResult<Response> {
+    /// Provides timestamp for given `digest` (hex encoded) made with `algorithm`
+    /// or `404 Not Found` in the case of timestamp for data doesn't exist.
+    ///
+    /// `algorithm` is one of `sha256`, `sha512` or `sha3_256`.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamp/digest/sha3_256/644bcc7e564373040999aac89e7622f3ca71fba1d972fd94a31c3bfbf24e3938
+    ///
+    /// {
+    ///   "algorithm": 2,
+    ///   "data_hash": "0d8e4a4c...",
+    ///   "digest": "644bcc7e564373040999aac89e7622f3ca71fba1d972fd94a31c3bfbf24e3938",
+    ///   ...
+    /// }
+    ///
+    /// `data_hash` is the key of the timestamp, see `HashAlgorithm::storage_key`.
+    ///
+    fn get_digest_timestamp(&self, req: &mut Request) -> IronResult<Response> {
+        let (algorithm, digest) = {
+            let params = req.extensions.get::<Router>().unwrap();
+            let algorithm = params.find("algorithm").unwrap_or("").parse::<HashAlgorithm>();
+            (algorithm, params.find("digest").unwrap_or("").to_owned())
+        };
+        let algorithm = algorithm.map_err(ApiError::BadRequest)?;
+        let digest = digest_param(algorithm, &digest)?;
+
         let snapshot = self.blockchain.snapshot();
         let schema = TimestampingSchema::new(snapshot);
+
+        if let Some(timestamp) = schema.timestamp(&algorithm.storage_key(&digest)) {
+            self.ok_response(&serde_json::to_value(timestamp).unwrap())
+        } else {
+            self.not_found_response(&serde_json::to_value("Timestamp not found").unwrap())
+        }
+    }
+
+    /// Checks whether the document is timestamped, the document is provided either
+    /// as JSON `TransactionRequestBase64` or as the raw request body
+    ///
+    /// The document is hashed the same way `post_base64` and `post_upload` do, the raw body
+    /// is hashed with the `algorithm` query param, `sha256` by default.
+    /// `doc_metainfo` of the JSON request is ignored.
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/verify
+    /// Request Payload:
+    /// {
+    ///   "data": "aGVsbG8gd29ybGQ="
+    /// }
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+    ///   "exists": true,
+    ///   "proof": { "block_proof": { ... }, "to_table": { ... }, "to_timestamp": { ... } },
+    ///   "timestamp": { "data_hash": "b94d27b9...", "timestamp": "1522587390", ... }
+    /// }
+    ///
+    /// `timestamp` and `proof` are `null` in the case of the document isn't timestamped.
+    /// `proof` is `null` as well for the schema version 0 timestamp until it's migrated
+    /// by the validator attestation.
+    ///
+    fn post_verify(&self, req: &mut Request) -> IronResult<Response> {
+        let is_json = match req.headers.get::<ContentType>() {
+            Some(&ContentType(Mime(TopLevel::Application, SubLevel::Json, _))) => true,
+            _ => false,
+        };
+        let data_hash = if is_json {
+            let request = match req.get::<bodyparser::Struct<TransactionRequestBase64>>() {
+                Ok(Some(request)) => request,
+                Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+                Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+            };
+            let algorithm = request.algorithm.unwrap_or_default();
+            algorithm.storage_key(&algorithm.digest(&request.data))
+        } else {
+            let algorithm: HashAlgorithm =
+                self.optional_param(req, "algorithm")?.unwrap_or_default();
+            algorithm.storage_key(&body_digest(req, algorithm)?)
+        };
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+        let timestamp = schema.timestamp(&data_hash);
+        let proof = if schema.timestamps().contains(&data_hash) {
+            Some(TimestampProof::new(&snapshot, &data_hash))
+        } else {
+            None
+        };
+
+        let json = VerificationResponse {
+            exists: timestamp.is_some(),
+            data_hash,
+            timestamp,
+            proof,
+        };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Provides Merkle proof of the timestamp for given `data_hash` or `404 Not Found`
+    /// in the case of timestamp for data doesn't exist or is not migrated from the schema
+    /// version 0 table yet.
+    ///
+    /// The proof binds the timestamp to the `state_hash` of the latest committed block,
+    /// so it can be checked offline against the validators consensus keys.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamp/b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb01/proof
+    ///
+    /// {
+    ///   "block_proof": {
+    ///     "block": { "height": "104", "state_hash": "494aad03...", ... },
+    ///     "precommits": [ ... ]
+    ///   },
+    ///   "to_table": { ... },
+    ///   "to_timestamp": { ... }
+    /// }
+    ///
+    fn get_timestamp_proof(&self, req: &mut Request) -> IronResult<Response> {
+        let hash: Hash = hex_param(req, "data_hash")?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+
+        if schema.timestamps().contains(&hash) {
+            let proof = TimestampProof::new(&snapshot, &hash);
+            self.ok_response(&serde_json::to_value(proof).unwrap())
+        } else {
+            self.proof_not_found(&schema, &hash)
+        }
+    }
+
+    /// `404 Not Found` response of the proof requested for the timestamp out of
+    /// the timestamps table
+    fn proof_not_found<T: AsRef<Snapshot>>(
+        &self,
+        schema: &TimestampingSchema<T>,
+        hash: &Hash
+    ) -> IronResult<Response> {
+        let message = if schema.legacy_timestamps().contains(hash) {
+            "Timestamp is not migrated yet, the proof is available after attestation"
+        } else {
+            "Timestamp not found"
+        };
+        self.not_found_response(&serde_json::to_value(message).unwrap())
+    }
+
+    /// Provides OpenTimestamps-compatible `.ots` proof of the timestamp for given `data_hash`
+    /// or `404 Not Found` in the case of timestamp for data doesn't exist or is not migrated
+    /// from the schema version 0 table yet.
+    ///
+    /// The proof commits the document hash to the hash of the stored `Timestamp`, which is
+    /// attested by the latest committed block with the `EXONUMTS` attestation.
+    /// The attestation payload embeds the `TimestampProof`, see `verification::ots`.
+    ///
+    /// Only SHA-256 timestamps are exported, `400 Bad Request` is returned for the others.
+    /// `422 Unprocessable Entity` is returned in the case of the proof doesn't fit into
+    /// the attestation payload limit of the OpenTimestamps tooling (`MAX_PAYLOAD_LEN`).
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamp/b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb01/ots
+    ///
+    fn get_timestamp_ots(&self, req: &mut Request) -> IronResult<Response> {
+        let hash: Hash = hex_param(req, "data_hash")?;
+
+        let snapshot = self.blockchain.snapshot();
+        match OtsProof::new(&snapshot, &hash) {
+            Ok(proof) => Ok(Response::with((
+                Status::Ok,
+                Header(ContentType("application/octet-stream".parse().unwrap())),
+                proof.to_bytes(),
+            ))),
+            Err(OtsError::TimestampNotFound) => {
+                self.proof_not_found(&TimestampingSchema::new(&snapshot), &hash)
+            }
+            Err(e @ OtsError::UnsupportedAlgorithm) => Err(ApiError::BadRequest(e.to_string()))?,
+            Err(e @ OtsError::UnexpectedLayout) => {
+                Err(ApiError::InternalError(e.to_string().into()))?
+            }
+            Err(e) => Ok(Response::with((
+                Status::UnprocessableEntity,
+                Header(ContentType::json()),
+                serde_json::to_string(&e.to_string()).unwrap(),
+            ))),
+        }
+    }
+
+    /// Provides execution status of the transaction `tx_hash` or `404 Not Found`
+    /// in the case of transaction is not known to the node.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamp/tx/d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67/status
+    ///
+    /// {
+    ///   "status": "failed",
+    ///   "height": "104",
+    ///   "code": 0,
+    ///   "description": "Document already exists"
+    /// }
+    ///
+    /// `status` is one of `pending`, `committed` (with `height`) or `failed`.
+    ///
+    fn get_transaction_status(&self, req: &mut Request) -> IronResult<Response> {
+        let tx_hash: Hash = hex_param(req, "tx_hash")?;
+
+        let snapshot = self.blockchain.snapshot();
+        let status = TransactionStatus::new(&snapshot, &tx_hash);
+
+        if status == TransactionStatus::Unknown {
+            self.not_found_response(&serde_json::to_value(&status).unwrap())
+        } else {
+            self.ok_response(&serde_json::to_value(&status).unwrap())
+        }
+    }
+
+    /// Provides the full version chain of the document `data_hash` belongs to
+    /// or `404 Not Found` in the case of timestamp for data doesn't exist.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamp/a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926/versions
+    ///
+    /// {
+    ///   "document_id": "b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60",
+    ///   "versions": [
+    ///     { "data_hash": "b32b3423...", "timestamp": "1522587390", ... },
+    ///     { "data_hash": "a9221767...", "timestamp": "1522587412", ... }
+    ///   ]
+    /// }
+    ///
+    fn get_document_versions(&self, req: &mut Request) -> IronResult<Response> {
+        let hash: Hash = hex_param(req, "data_hash")?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+
+        if schema.timestamp(&hash).is_none() {
+            return self.not_found_response(&serde_json::to_value("Timestamp not found").unwrap());
+        }
+
+        let document_id = schema.document_id(&hash);
+        let hashes = schema.document_versions(&document_id);
+        let versions: Vec<Timestamp> = if hashes.is_empty() {
+            schema.timestamp(&hash).into_iter().collect()
+        } else {
+            hashes.iter().filter_map(|hash| schema.timestamp(&hash)).collect()
+        };
+
+        let json = DocumentVersions { document_id, versions };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Provides timestamps made by `pub_key` in the order of creation
+    ///
+    /// Query params:
+    ///  - `offset` - position of the first timestamp in the author list, 0 by default;
+    ///  - `limit` - positive page size, `DEFAULT_PAGE_LIMIT` by default, at most `MAX_PAGE_LIMIT`.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/author/6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924235/timestamps?offset=0&limit=2
+    ///
+    /// {
+    ///   "timestamps": [
+    ///     { "author": "6ce29b2d...", "data_hash": "b900b8e9...", ... },
+    ///     { "author": "6ce29b2d...", "data_hash": "a92217670...", ... }
+    ///   ],
+    ///   "next": 2
+    /// }
+    ///
+    fn get_author_timestamps(&self, req: &mut Request) -> IronResult<Response> {
+        let author: PublicKey = hex_param(req, "pub_key")?;
+        let offset: u64 = self.optional_param(req, "offset")?.unwrap_or(0);
+        let limit = self.optional_param(req, "limit")?;
+        let limit = page_limit(limit, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT)?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+        let hashes = schema.author_timestamps(&author);
+
+        let timestamps: Vec<Timestamp> = hashes
+            .iter_from(offset)
+            .take(limit as usize)
+            .filter_map(|hash| schema.timestamp(&hash))
+            .collect();
+        let next = offset.checked_add(limit).filter(|&next| next < hashes.len());
+
+        let page = TimestampsPage { timestamps, next };
+        self.ok_response(&serde_json::to_value(&page).unwrap())
+    }
+
+    /// Provides stored timestamps in the order of `data_hash`, page by page
+    ///
+    /// Query params:
+    ///  - `from` - `data_hash` to start the page from, the `next` cursor of the previous page;
+    ///  - `limit` - positive page size, `DEFAULT_PAGE_LIMIT` by default, at most `MAX_PAGE_LIMIT`;
+    ///  - `since`, `until` - optional Unix time range of `Timestamp::timestamp`, both inclusive.
+    ///
+    /// Schema version 0 records are listed as well until they are migrated, such records
+    /// are not attested and have no `TimestampProof` yet.
+    ///
+    /// At most `MAX_SCANNED_ENTRIES` timestamps are scanned per page, so the page filtered
+    /// by the time range may hold less than `limit` timestamps while `next` is provided.
+    /// Use `/v0/timestamps/by_time` to look up the time range without the full scan.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamps?limit=2&since=1522587000
+    ///
+    /// {
+    ///   "timestamps": [
+    ///     { "data_hash": "1cd143c7...", "timestamp": "1522587390", ... },
+    ///     { "data_hash": "6bb5b4df...", "timestamp": "1522587412", ... }
+    ///   ],
+    ///   "next": "a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926"
+    /// }
+    ///
+    fn get_timestamps(&self, req: &mut Request) -> IronResult<Response> {
+        let from: Option<String> = self.optional_param(req, "from")?;
+        let from = match from {
+            Some(from) => Hash::from_hex(&from)
+                .map_err(|_| ApiError::BadRequest("Invalid request param: `from`".into()))?,
+            None => Hash::zero(),
+        };
+        let limit = self.optional_param(req, "limit")?;
+        let limit = page_limit(limit, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT)? as usize;
+        let since: Option<i64> = self.optional_param(req, "since")?;
+        let until: Option<i64> = self.optional_param(req, "until")?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
         let idx = schema.timestamps();
-        let timestamps: Vec<Timestamp> = idx.values().collect();
+        let legacy_idx = schema.legacy_timestamps();
+        let mut current = idx.iter_from(&from).peekable();
+        let mut legacy = legacy_idx.iter_from(&from).peekable();
+
+        let mut timestamps = Vec::new();
+        let mut next = None;
+        let mut scanned = 0;
+        loop {
+            // Both tables are ordered by `data_hash`, so they are merged into a single listing
+            let from_legacy = match (current.peek(), legacy.peek()) {
+                (Some(&(ref data_hash, _)), Some(&(ref legacy_hash, _))) => legacy_hash < data_hash,
+                (None, Some(_)) => true,
+                (_, None) => false,
+            };
+            let entry = if from_legacy {
+                legacy
+                    .next()
+                    .map(|(data_hash, legacy)| (data_hash, Timestamp::from_legacy(&legacy)))
+            } else {
+                current.next()
+            };
+            let (data_hash, timestamp) = match entry {
+                Some(entry) => entry,
+                None => break,
+            };
+
+            if timestamps.len() == limit || scanned == MAX_SCANNED_ENTRIES {
+                next = Some(data_hash);
+                break;
+            }
+            scanned += 1;
+            if since.map_or(true, |since| timestamp.timestamp() >= since)
+                && until.map_or(true, |until| timestamp.timestamp() <= until)
+            {
+                timestamps.push(timestamp);
+            }
+        }
+
+        let page = TimestampsPage { timestamps, next };
+        self.ok_response(&serde_json::to_value(&page).unwrap())
+    }
+
+    /// Provides timestamps made in the given time range in the order of creation, page by page
+    ///
+    /// Query params:
+    ///  - `since`, `until` - optional Unix time range of `Timestamp::timestamp`, both inclusive;
+    ///  - `from` - position to start the page from, the `next` cursor of the previous page;
+    ///  - `limit` - positive page size, `DEFAULT_PAGE_LIMIT` by default, at most `MAX_PAGE_LIMIT`.
+    ///
+    /// # Example
+    /// GET: /api/services/timestamping/v0/timestamps/by_time?since=1522587000&until=1522590600&limit=2
+    ///
+    /// {
+    ///   "timestamps": [
+    ///     { "data_hash": "b32b3423...", "timestamp": "1522587390", ... },
+    ///     { "data_hash": "a9221767...", "timestamp": "1522587390", ... }
+    ///   ],
+    ///   "next": 14
+    /// }
+    ///
+    fn get_timestamps_by_time(&self, req: &mut Request) -> IronResult<Response> {
+        let since: Option<i64> = self.optional_param(req, "since")?;
+        let until: Option<i64> = self.optional_param(req, "until")?;
+        let from: Option<u64> = self.optional_param(req, "from")?;
+        let limit = self.optional_param(req, "limit")?;
+        let limit = page_limit(limit, DEFAULT_PAGE_LIMIT, MAX_PAGE_LIMIT)?;
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+        let by_time = schema.timestamps_by_time();
+        let timestamps_idx = schema.timestamps();
+
+        let start = since.map_or(0, |since| schema.time_position(since)).max(from.unwrap_or(0));
+        let mut matched = by_time
+            .iter_from(start)
+            .filter_map(|data_hash| timestamps_idx.get(&data_hash))
+            .take_while(|timestamp| until.map_or(true, |until| timestamp.timestamp() <= until));
+        let timestamps: Vec<Timestamp> = matched.by_ref().take(limit as usize).collect();
+        let next = if matched.next().is_some() {
+            Some(start + timestamps.len() as u64)
+        } else {
+            None
+        };
 
-        self.ok_response(&serde_json::to_value(&timestamps).unwrap())
+        let page = TimestampsPage { timestamps, next };
+        self.ok_response(&serde_json::to_value(&page).unwrap())
     }
 
     /// Creates timestamp for given `data_hash`
@@ -127,52 +672,524 @@ impl TimestampingApi {
     /// POST: /api/services/timestamping/v0/timestamp/hash
     /// Request Payload:
     /// {
-    ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02"
+    ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
+    ///   "doc_metainfo": "contract.pdf"
     /// }
     ///
+    /// `doc_metainfo` is optional, at most `MAX_METAINFO_LEN` bytes.
+    ///
     /// Response:
     /// {
     ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
     ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
     /// }
     ///
+    /// With `wait` query param the request blocks up to `wait` seconds (at most `MAX_WAIT_SECS`)
+    /// until the transaction is committed. At most `MAX_WAITERS` requests wait simultaneously,
+    /// the rest are rejected with `503 Service Unavailable`.
+    ///
+    /// POST: /api/services/timestamping/v0/timestamp/hash?wait=10
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
+    ///   "height": "104",
+    ///   "status": "committed",
+    ///   "timestamp": { "data_hash": "b900b8e9...", "height": "104", ... },
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    /// `status` is `pending` in the case of timeout, see `get_transaction_status` for details.
+    ///
     fn post_hash(&self, req: &mut Request) -> IronResult<Response>{
-        let hash: Hash = match req.get::<bodyparser::Struct<TransactionRequestHash>>() {
-            Ok(Some(request)) => request.data_hash,
+        let wait = self.wait_param(req)?;
+        let request = match req.get::<bodyparser::Struct<TransactionRequestHash>>() {
+            Ok(Some(request)) => request,
             Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
             Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
         };
-        self.send_tx(&hash)
+        self.send_tx(&request.data_hash, request.doc_metainfo.as_ref().map(String::as_str), wait)
     }
 
     /// Creates timestamp for given `data` (base64 encoded)
+    ///
+    /// `data` is hashed with the optional `algorithm`, SHA-256 by default.
+    /// Supports `wait` query param the same way `post_hash` does.
     fn post_base64(&self, req: &mut Request) -> IronResult<Response>{
-        let hash: Hash = match req.get::<bodyparser::Struct<TransactionRequestBase64>>() {
-            Ok(Some(request)) => hash(request.data.as_slice()),
+        let wait = self.wait_param(req)?;
+        let request = match req.get::<bodyparser::Struct<TransactionRequestBase64>>() {
+            Ok(Some(request)) => request,
             Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
             Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
         };
-        self.send_tx(&hash)
+        let metainfo = request.doc_metainfo.as_ref().map(String::as_str);
+        match request.algorithm.unwrap_or_default() {
+            HashAlgorithm::Sha256 => self.send_tx(&hash(request.data.as_slice()), metainfo, wait),
+            algorithm => {
+                let digest = algorithm.digest(&request.data);
+                self.send_digest_tx(algorithm, &digest, metainfo, wait)
+            }
+        }
     }
 
-    /// Common `send transaction` implementation
-    fn send_tx(&self, hash: &Hash) -> IronResult<Response> {
-        let tx = Tx::new(
+    /// Creates timestamp for the document uploaded as the raw request body
+    ///
+    /// The body is hashed chunk by chunk while it streams in, so the document is never
+    /// held in memory and its size isn't limited.
+    ///
+    /// Query params:
+    ///  - `algorithm` - hash algorithm of the document, `sha256` by default;
+    ///  - `doc_metainfo` - optional document metainfo, at most `MAX_METAINFO_LEN` bytes;
+    ///  - `wait` - supported the same way `post_hash` does.
+    ///
+    /// # Example
+    /// $ curl -H "Content-Type: application/octet-stream" --data-binary @video.mp4 \
+    ///     "http://127.0.0.1:8200/api/services/timestamping/v0/timestamp/upload?doc_metainfo=video.mp4"
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    fn post_upload(&self, req: &mut Request) -> IronResult<Response> {
+        let wait = self.wait_param(req)?;
+        let algorithm: HashAlgorithm = self.optional_param(req, "algorithm")?.unwrap_or_default();
+        let metainfo: Option<String> = self.optional_param(req, "doc_metainfo")?;
+
+        let digest = body_digest(req, algorithm)?;
+        self.send_digest_tx(algorithm, &digest, metainfo.as_ref().map(String::as_str), wait)
+    }
+
+    /// Creates timestamp for given `digest` (hex encoded) made with `algorithm`
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/timestamp/digest
+    /// Request Payload:
+    /// {
+    ///   "algorithm": "sha512",
+    ///   "digest": "309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f",
+    ///   "doc_metainfo": "archive.tar"
+    /// }
+    ///
+    /// `algorithm` is one of `sha256`, `sha512` or `sha3_256`.
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "0d8e4a4c3f5e3a8a8b4c4f1e7b6a0d0c2f3d8e1b7a6c5d4e3f2a1b0c9d8e7f6a",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    /// `data_hash` is the key of the timestamp, see `HashAlgorithm::storage_key`.
+    /// Supports `wait` query param the same way `post_hash` does.
+    ///
+    fn post_digest(&self, req: &mut Request) -> IronResult<Response> {
+        let wait = self.wait_param(req)?;
+        let request = match req.get::<bodyparser::Struct<TransactionRequestDigest>>() {
+            Ok(Some(request)) => request,
+            Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+            Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+        };
+        let digest = digest_param(request.algorithm, &request.digest)?;
+        let metainfo = request.doc_metainfo.as_ref().map(String::as_str);
+        self.send_digest_tx(request.algorithm, &digest, metainfo, wait)
+    }
+
+    /// Creates timestamps for the batch of `data_hash`es or `data` (base64 encoded),
+    /// one transaction per item
+    ///
+    /// Items are validated independently, rejected items don't affect the others.
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/timestamp/batch
+    /// Request Payload:
+    /// [
+    ///   { "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02" },
+    ///   { "data": "aGVsbG8gd29ybGQ=" },
+    ///   { "data": "not base64" }
+    /// ]
+    ///
+    /// Response:
+    /// [
+    ///   {
+    ///     "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
+    ///     "error": null,
+    ///     "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    ///   },
+    ///   {
+    ///     "data_hash": "b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380ee9088f7ace2efcde9",
+    ///     "error": null,
+    ///     "tx_hash": "6f4ad4d2291d3fc41d77840a153f808235a4b67b0dd6245e145c21ed4029371f"
+    ///   },
+    ///   {
+    ///     "data_hash": null,
+    ///     "error": "Invalid base64 data: Invalid byte 32, offset 3.",
+    ///     "tx_hash": null
+    ///   }
+    /// ]
+    ///
+    fn post_batch(&self, req: &mut Request) -> IronResult<Response> {
+        let items = match req.get::<bodyparser::Struct<Vec<BatchItemRequest>>>() {
+            Ok(Some(items)) => items,
+            Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+            Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+        };
+        if items.len() > MAX_BATCH_SIZE {
+            Err(ApiError::BadRequest(format!("Batch is larger than {} items", MAX_BATCH_SIZE)))?
+        }
+
+        let snapshot = self.blockchain.snapshot();
+        let schema = TimestampingSchema::new(&snapshot);
+        let mut seen = HashSet::new();
+
+        let mut results = Vec::with_capacity(items.len());
+        for item in items {
+            let data_hash = match (item.data_hash, item.data) {
+                (Some(data_hash), None) => Ok(data_hash),
+                (None, Some(data)) => base64::decode(&data)
+                    .map(|data| hash(&data))
+                    .map_err(|e| format!("Invalid base64 data: {}", e)),
+                _ => Err("Either `data_hash` or `data` must be provided".to_owned()),
+            };
+            let data_hash = match data_hash {
+                Ok(data_hash) => data_hash,
+                Err(error) => {
+                    results.push(BatchItemResponse {
+                        tx_hash: None,
+                        data_hash: None,
+                        error: Some(error),
+                    });
+                    continue;
+                }
+            };
+
+            let tx_hash = if schema.timestamp(&data_hash).is_some() {
+                Err(TimestampingError::DocumentAlreadyExists.to_string())
+            } else if !seen.insert(data_hash) {
+                Err("Duplicate document in batch".to_owned())
+            } else {
+                let tx = Tx::new(&self.service_keys.0, &data_hash, &self.service_keys.1);
+                self.submit(tx.into(), data_hash)
+                    .map(|response| response.tx_hash)
+                    .map_err(|e| e.to_string())
+            };
+
+            results.push(BatchItemResponse {
+                tx_hash: tx_hash.as_ref().ok().cloned(),
+                data_hash: Some(data_hash),
+                error: tx_hash.err(),
+            });
+        }
+
+        self.ok_response(&serde_json::to_value(&results).unwrap())
+    }
+
+    /// Creates single timestamp for the Merkle root of `data_hashes`
+    ///
+    /// The tree is built the same way as `MerkleBatch` does, so clients may build it
+    /// themselves and submit `TxBatch` via `/v0/timestamp/signed`.
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/timestamp/merkle
+    /// Request Payload:
+    /// {
+    ///   "data_hashes": [
+    ///     "b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60",
+    ///     "a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926"
+    ///   ]
+    /// }
+    ///
+    /// Response:
+    /// {
+    ///   "paths": [
+    ///     {
+    ///       "data_hash": "b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60",
+    ///       "path": { "siblings": [ { "right": "5d1ab2bb..." } ] }
+    ///     },
+    ///     {
+    ///       "data_hash": "a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926",
+    ///       "path": { "siblings": [ { "left": "0e7c6fc1..." } ] }
+    ///     }
+    ///   ],
+    ///   "root_hash": "3b4a39f0a9b58a1ec1e1c6b49e9d4cd5f5bd0e1d4a0e4b1f8a6b0a1b2c3d4e5f",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    fn post_merkle(&self, req: &mut Request) -> IronResult<Response> {
+        let request = match req.get::<bodyparser::Struct<TransactionRequestMerkle>>() {
+            Ok(Some(request)) => request,
+            Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+            Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+        };
+        let batch = match MerkleBatch::new(&request.data_hashes) {
+            Some(batch) => batch,
+            None => Err(ApiError::BadRequest("Empty `data_hashes`".into()))?,
+        };
+
+        let root_hash = batch.root();
+        let tx = TxBatch::new(
+            &self.service_keys.0,
+            &root_hash,
+            batch.len() as u64,
+            &self.service_keys.1
+        );
+        let tx_hash = self.submit(tx.into(), root_hash)?.tx_hash;
+
+        let paths = request
+            .data_hashes
+            .iter()
+            .enumerate()
+            .map(|(index, data_hash)| MerklePathResponse {
+                data_hash: *data_hash,
+                path: batch.path(index).unwrap(),
+            })
+            .collect();
+
+        let json = TransactionResponseMerkle { tx_hash, root_hash, paths };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Creates timestamp for the new version of the document timestamped as `prev_hash`
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/timestamp/version
+    /// Request Payload:
+    /// {
+    ///   "data_hash": "a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926",
+    ///   "prev_hash": "b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60",
+    ///   "doc_metainfo": "contract v2"
+    /// }
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "a92217670661fbb669142f67dc7810cd3afcd2691f4195410fb84c93e4c47926",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    fn post_version(&self, req: &mut Request) -> IronResult<Response> {
+        let request = match req.get::<bodyparser::Struct<TransactionRequestVersion>>() {
+            Ok(Some(request)) => request,
+            Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+            Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+        };
+        let tx = TxNewVersion::new(
             &self.service_keys.0,
-            hash,
+            &request.data_hash,
+            &request.prev_hash,
+            request.doc_metainfo.as_ref().map_or("", String::as_str),
             &self.service_keys.1
         );
+        self.broadcast(tx.into(), request.data_hash)
+    }
+
+    /// Broadcasts `Tx`, `TxWithMetainfo`, `TxNewVersion`, `TxBatch` or `TxDigest` signed by the client
+    ///
+    /// The transaction is sent unchanged, so the timestamp can be traced to the client `pub_key`.
+    ///
+    /// # Example
+    /// POST: /api/services/timestamping/v0/timestamp/signed
+    /// Request Payload:
+    /// {
+    ///   "body": {
+    ///     "pub_key": "6ce29b2d3ecadc434107ce52c287001c968a1b6eca3e5a1eb62a2419e2924235",
+    ///     "doc_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02"
+    ///   },
+    ///   "message_id": 0,
+    ///   "protocol_version": 0,
+    ///   "service_id": 42,
+    ///   "signature": "9f684227f1de663775848b3db656bca685e085391e2b00b0e115679fd45443ef58a5abeb555ab3d5f7a3cd27955a2079e5fd486743f36515c8e5bea07992100b"
+    /// }
+    ///
+    /// Response:
+    /// {
+    ///   "data_hash": "b900b8e9bba54eae47f6de08e8ff024e841274927d202a45551e875ced0eeb02",
+    ///   "tx_hash": "d597703ee22849854ea8e9b322054e21d2ff15e9a10195681833976d83842d67"
+    /// }
+    ///
+    fn post_signed(&self, req: &mut Request) -> IronResult<Response> {
+        let tx = match req.get::<bodyparser::Struct<TimestampingTransactions>>() {
+            Ok(Some(tx)) => tx,
+            Ok(None) => Err(ApiError::BadRequest("Empty request body".into()))?,
+            Err(e) => Err(ApiError::BadRequest(e.to_string()))?,
+        };
+        let data_hash = match tx {
+            TimestampingTransactions::Tx(ref tx) => *tx.doc_hash(),
+            TimestampingTransactions::TxWithMetainfo(ref tx) => *tx.doc_hash(),
+            TimestampingTransactions::TxNewVersion(ref tx) => *tx.doc_hash(),
+            TimestampingTransactions::TxBatch(ref tx) => *tx.root_hash(),
+            TimestampingTransactions::TxDigest(ref tx) => {
+                let algorithm = HashAlgorithm::from_id(tx.algorithm())
+                    .ok_or_else(|| ApiError::BadRequest("Unsupported hash algorithm".into()))?;
+                if tx.digest().len() != algorithm.digest_len() {
+                    Err(ApiError::BadRequest("Invalid digest length".into()))?
+                }
+                algorithm.storage_key(tx.digest())
+            }
+            _ => Err(ApiError::BadRequest("Unsupported transaction type".into()))?,
+        };
         let transaction: Box<Transaction> = tx.into();
+        if !transaction.verify() {
+            Err(ApiError::BadRequest("Invalid transaction signature".into()))?
+        }
+        self.broadcast(transaction, data_hash)
+    }
+
+    /// Common `send transaction` implementation
+    fn send_tx(
+        &self,
+        hash: &Hash,
+        metainfo: Option<&str>,
+        wait: Option<Duration>
+    ) -> IronResult<Response> {
+        let transaction: Box<Transaction> = match metainfo {
+            Some(metainfo) => TxWithMetainfo::new(
+                &self.service_keys.0,
+                hash,
+                metainfo,
+                &self.service_keys.1
+            ).into(),
+            None => Tx::new(
+                &self.service_keys.0,
+                hash,
+                &self.service_keys.1
+            ).into(),
+        };
+        match wait {
+            Some(timeout) => self.broadcast_and_wait(transaction, hash.clone(), timeout),
+            None => self.broadcast(transaction, hash.clone()),
+        }
+    }
+
+    /// Common `send transaction` implementation for the `digest` made with `algorithm`
+    ///
+    /// SHA-256 digests are timestamped with `Tx` / `TxWithMetainfo` the same way `send_tx` does.
+    fn send_digest_tx(
+        &self,
+        algorithm: HashAlgorithm,
+        digest: &[u8],
+        metainfo: Option<&str>,
+        wait: Option<Duration>
+    ) -> IronResult<Response> {
+        if algorithm == HashAlgorithm::Sha256 {
+            return self.send_tx(&algorithm.storage_key(digest), metainfo, wait);
+        }
+
+        let data_hash = algorithm.storage_key(digest);
+        let transaction: Box<Transaction> = TxDigest::new(
+            &self.service_keys.0,
+            algorithm.id(),
+            digest,
+            metainfo.unwrap_or(""),
+            &self.service_keys.1
+        ).into();
+        match wait {
+            Some(timeout) => self.broadcast_and_wait(transaction, data_hash, timeout),
+            None => self.broadcast(transaction, data_hash),
+        }
+    }
+
+    /// Sends `transaction` to the network and waits up to `timeout` until it's committed
+    ///
+    /// Responds with `503 Service Unavailable` without sending the transaction
+    /// in the case of `MAX_WAITERS` requests are already waiting.
+    fn broadcast_and_wait(
+        &self,
+        transaction: Box<Transaction>,
+        data_hash: Hash,
+        timeout: Duration
+    ) -> IronResult<Response> {
+        let _waiter = match self.waiters.acquire() {
+            Some(waiter) => waiter,
+            None => {
+                return Ok(Response::with((
+                    Status::ServiceUnavailable,
+                    Header(ContentType::json()),
+                    "\"Too many requests waiting for commit\"",
+                )))
+            }
+        };
+        let tx_hash = self.submit(transaction, data_hash)?.tx_hash;
+        let status = TransactionStatus::wait_for_commit(&self.blockchain, &tx_hash, timeout);
+        let timestamp = match status {
+            TransactionStatus::Committed { .. } => {
+                TimestampingSchema::new(self.blockchain.snapshot()).timestamp(&data_hash)
+            }
+            _ => None,
+        };
+
+        let json = CommittedTransactionResponse { tx_hash, data_hash, status, timestamp };
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Parses `wait` query param, timeout in seconds
+    fn wait_param(&self, req: &mut Request) -> IronResult<Option<Duration>> {
+        let wait: Option<u64> = self.optional_param(req, "wait")?;
+        Ok(wait
+            .filter(|&secs| secs > 0)
+            .map(|secs| Duration::from_secs(secs.min(MAX_WAIT_SECS))))
+    }
+
+    /// Sends `transaction` to the network and responds with its hash
+    fn broadcast(&self, transaction: Box<Transaction>, data_hash: Hash) -> IronResult<Response> {
+        let json = self.submit(transaction, data_hash)?;
+        self.ok_response(&serde_json::to_value(&json).unwrap())
+    }
+
+    /// Sends `transaction` to the network
+    fn submit(
+        &self,
+        transaction: Box<Transaction>,
+        data_hash: Hash
+    ) -> Result<TransactionResponse, ApiError> {
         let tx_hash = transaction.hash();
-        let data_hash = hash.clone();
 
         self.channel.send(transaction).map_err(ApiError::from)?;
 
-        let json = TransactionResponse { tx_hash, data_hash };
-        self.ok_response(&serde_json::to_value(&json).unwrap())
+        Ok(TransactionResponse { tx_hash, data_hash })
     }
 }
 
+/// Parses hex encoded value from the route parameter with the given `name`
+fn hex_param<T>(req: &Request, name: &str) -> IronResult<T>
+where
+    T: FromHex,
+    T::Error: Error + Send + 'static,
+{
+    let param = req.extensions.get::<Router>().unwrap().find(name).unwrap_or("");
+    T::from_hex(param).map_err(|e| {
+        IronError::new(e, (
+            Status::BadRequest,
+            Header(ContentType::json()),
+            format!("\"Invalid request param: `{}`\"", name),
+        ))
+    })
+}
+
+/// Hashes the raw request body with `algorithm` chunk by chunk
+fn body_digest(req: &mut Request, algorithm: HashAlgorithm) -> IronResult<Vec<u8>> {
+    let mut stream = DigestStream::new(algorithm);
+    let mut chunk = vec![0u8; UPLOAD_CHUNK_SIZE];
+    loop {
+        match req.body.read(&mut chunk) {
+            Ok(0) => break,
+            Ok(len) => stream.update(&chunk[..len]),
+            Err(ref e) if e.kind() == ErrorKind::Interrupted => continue,
+            Err(e) => Err(ApiError::BadRequest(format!("Couldn't read request body: {}", e)))?,
+        }
+    }
+    Ok(stream.finish())
+}
+
+/// Parses hex encoded `digest` made with `algorithm`
+fn digest_param(algorithm: HashAlgorithm, digest: &str) -> Result<Vec<u8>, ApiError> {
+    let digest = Vec::<u8>::from_hex(digest)
+        .map_err(|_| ApiError::BadRequest("Invalid request param: `digest`".into()))?;
+    if digest.len() != algorithm.digest_len() {
+        Err(ApiError::BadRequest("Invalid digest length".into()))?
+    }
+    Ok(digest)
+}
+
 /// `Api` trait implementation.
 ///
 /// `Api` facilitates conversion between transactions/read requests and REST
@@ -185,15 +1202,56 @@ impl Api for TimestampingApi {
         let self_ = self.clone();
         let post_base64 = move |req: &mut Request| self_.post_base64(req);
         let self_ = self.clone();
+        let post_digest = move |req: &mut Request| self_.post_digest(req);
+        let self_ = self.clone();
+        let post_upload = move |req: &mut Request| self_.post_upload(req);
+        let self_ = self.clone();
+        let post_verify = move |req: &mut Request| self_.post_verify(req);
+        let self_ = self.clone();
+        let post_signed = move |req: &mut Request| self_.post_signed(req);
+        let self_ = self.clone();
+        let post_version = move |req: &mut Request| self_.post_version(req);
+        let self_ = self.clone();
+        let post_batch = move |req: &mut Request| self_.post_batch(req);
+        let self_ = self.clone();
+        let post_merkle = move |req: &mut Request| self_.post_merkle(req);
+        let self_ = self.clone();
+        let get_document_versions = move |req: &mut Request| self_.get_document_versions(req);
+        let self_ = self.clone();
         let get_timestamp = move |req: &mut Request| self_.get_timestamp(req);
         let self_ = self.clone();
+        let get_digest_timestamp = move |req: &mut Request| self_.get_digest_timestamp(req);
+        let self_ = self.clone();
+        let get_timestamp_proof = move |req: &mut Request| self_.get_timestamp_proof(req);
+        let self_ = self.clone();
+        let get_timestamp_ots = move |req: &mut Request| self_.get_timestamp_ots(req);
+        let self_ = self.clone();
+        let get_author_timestamps = move |req: &mut Request| self_.get_author_timestamps(req);
+        let self_ = self.clone();
+        let get_transaction_status = move |req: &mut Request| self_.get_transaction_status(req);
+        let self_ = self.clone();
         let get_timestamps = move |req: &mut Request| self_.get_timestamps(req);
+        let self_ = self.clone();
+        let get_timestamps_by_time = move |req: &mut Request| self_.get_timestamps_by_time(req);
 
         // Bind handlers to specific routes.
         router.post("/v0/timestamp/hash", post_hash, "post_hash_rt`");
         router.post("/v0/timestamp/base64", post_base64, "post_base64_rt`");
+        router.post("/v0/timestamp/digest", post_digest, "post_digest_rt");
+        router.post("/v0/timestamp/upload", post_upload, "post_upload_rt");
+        router.post("/v0/verify", post_verify, "post_verify_rt");
+        router.post("/v0/timestamp/signed", post_signed, "post_signed_rt");
+        router.post("/v0/timestamp/version", post_version, "post_version_rt");
+        router.post("/v0/timestamp/batch", post_batch, "post_batch_rt");
+        router.post("/v0/timestamp/merkle", post_merkle, "post_merkle_rt");
         router.get("/v0/timestamp/:data_hash", get_timestamp, "get_timestamp_rt");
-        // optional
+        router.get("/v0/timestamp/digest/:algorithm/:digest", get_digest_timestamp, "get_digest_timestamp_rt");
+        router.get("/v0/timestamp/:data_hash/proof", get_timestamp_proof, "get_timestamp_proof_rt");
+        router.get("/v0/timestamp/:data_hash/ots", get_timestamp_ots, "get_timestamp_ots_rt");
+        router.get("/v0/timestamp/:data_hash/versions", get_document_versions, "get_document_versions_rt");
+        router.get("/v0/author/:pub_key/timestamps", get_author_timestamps, "get_author_timestamps_rt");
+        router.get("/v0/timestamp/tx/:tx_hash/status", get_transaction_status, "get_transaction_status_rt");
         router.get("/v0/timestamps", get_timestamps, "get_timestamps_rt");
+        router.get("/v0/timestamps/by_time", get_timestamps_by_time, "get_timestamps_by_time_rt");
     }
 }
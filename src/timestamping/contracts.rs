@@ -13,16 +13,94 @@
 // limitations under the License.
 
 
-use exonum::blockchain::{Transaction, ExecutionResult};
+use exonum::blockchain::{Schema, Transaction, ExecutionResult};
+use exonum::crypto::{self, Hash, PublicKey};
 use exonum::messages::Message;
-use exonum::storage::Fork;
+use exonum::storage::{Fork, StorageValue};
 use exonum_time::TimeSchema;
 
 
 use super::schema::{Timestamp, TimestampingSchema};
-use super::transactions::Tx;
+use super::transactions::{Tx, TxAttest, TxBatch, TxDigest, TxNewVersion, TxWithMetainfo,
+                          MAX_METAINFO_LEN};
+use super::digest::HashAlgorithm;
 use super::errors::Error;
 
+/// Receives a fork of the current blockchain state and put new `Timestamp`
+/// of SHA-256 `data_hash`.
+fn create_timestamp(
+    view: &mut Fork,
+    tx_hash: &Hash,
+    author: &PublicKey,
+    data_hash: &Hash,
+    metainfo: &str
+) -> ExecutionResult {
+    create_digest_timestamp(
+        view,
+        tx_hash,
+        author,
+        HashAlgorithm::Sha256,
+        data_hash.as_ref(),
+        metainfo
+    )
+}
+
+/// Receives a fork of the current blockchain state and put new `Timestamp`
+/// of `digest` made with `algorithm` by `HashAlgorithm::storage_key` as id.
+///
+/// Timestamp {
+///      signature: zero until attested by `TxAttest`
+///      timestamp: exonum_time::TimeService.consolidated_time()
+///      data_hash: algorithm.storage_key(digest)
+///      height: height of the block being executed
+///      signer: zero until attested by `TxAttest`
+///      author: author
+///      metainfo: metainfo
+///      tx_hash: tx_hash
+///      nanos: nanoseconds part of the consolidated time
+///      algorithm: algorithm id
+///      digest: digest
+/// }
+///
+fn create_digest_timestamp(
+    view: &mut Fork,
+    tx_hash: &Hash,
+    author: &PublicKey,
+    algorithm: HashAlgorithm,
+    digest: &[u8],
+    metainfo: &str
+) -> ExecutionResult {
+    let time = TimeSchema::new(&view).time().get();
+    let height = Schema::new(&view).height().next();
+    let data_hash = &algorithm.storage_key(digest);
+    let mut schema = TimestampingSchema::new(view);
+
+    match time {
+        Some(current_time) => {
+            if schema.timestamp(data_hash).is_none() {
+                let timestamp = Timestamp::from_parts(
+                    &current_time,
+                    algorithm,
+                    digest,
+                    author,
+                    height,
+                    metainfo,
+                    tx_hash
+                );
+                schema.timestamps_mut().put(data_hash, timestamp);
+                schema.timestamps_by_time_mut().push(*data_hash);
+                schema.author_timestamps_mut(author).push(*data_hash);
+                Ok(())
+            } else {
+                Err(Error::DocumentAlreadyExists)?
+            }
+        }
+        _ => {
+            Err(Error::TimeServiceError)?
+        }
+    }
+}
+
 /// Implementation of `Transaction` trait for `Tx`
 impl Transaction for Tx {
     /// Verifies the internal consistency of the transaction.
@@ -30,39 +108,422 @@ impl Transaction for Tx {
         self.verify_signature(self.pub_key())
     }
 
-    /// Receives a fork of the current blockchain state and put new `Timestamp`
-    /// by given `data_hash` as id.
-    ///
-    /// Timestamp {
-    ///      signature: hash(data_hash + timestamp)
-    ///      timestamp: exonum_time::TimeService.consolidated_time()
-    ///      data_hash: data_hash
-    /// }
+    /// Puts new `Timestamp` without metainfo.
+    fn execute(&self, view: &mut Fork) -> ExecutionResult {
+        create_timestamp(view, &Message::hash(self), self.pub_key(), self.doc_hash(), "")
+    }
+}
+
+/// Implementation of `Transaction` trait for `TxWithMetainfo`
+impl Transaction for TxWithMetainfo {
+    /// Verifies the internal consistency of the transaction.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    /// Puts new `Timestamp` with `doc_metainfo` of at most `MAX_METAINFO_LEN` bytes.
+    fn execute(&self, view: &mut Fork) -> ExecutionResult {
+        if self.doc_metainfo().len() > MAX_METAINFO_LEN {
+            Err(Error::MetainfoTooLong)?
+        }
+        create_timestamp(
+            view,
+            &Message::hash(self),
+            self.pub_key(),
+            self.doc_hash(),
+            self.doc_metainfo()
+        )
+    }
+}
+
+/// Implementation of `Transaction` trait for `TxNewVersion`
+impl Transaction for TxNewVersion {
+    /// Verifies the internal consistency of the transaction.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
+
+    /// Puts new `Timestamp` and appends it to the versions of the document `prev_hash` belongs to.
     ///
+    /// Only the author of the latest version can make the next one.
     fn execute(&self, view: &mut Fork) -> ExecutionResult {
-        let time = TimeSchema::new(&view).time().get();
-        let mut schema = TimestampingSchema::new(view);
+        if self.doc_metainfo().len() > MAX_METAINFO_LEN {
+            Err(Error::MetainfoTooLong)?
+        }
 
-        match time {
-            Some(current_time) => {
-                if schema.timestamp(self.doc_hash()).is_none() {
-                    let timestamp = Timestamp::from_parts(&current_time, self.doc_hash());
-                    schema.timestamps_mut().put(self.doc_hash(), timestamp);
-                    Ok(())
-                } else {
-                    Err(Error::DocumentAlreadyExists)?
+        let document_id = {
+            let schema = TimestampingSchema::new(&view);
+            let prev = match schema.timestamp(self.prev_hash()) {
+                Some(prev) => prev,
+                None => Err(Error::PreviousVersionNotFound)?,
+            };
+            if prev.author() != self.pub_key() {
+                Err(Error::NotDocumentAuthor)?
+            }
+            let document_id = schema.document_id(self.prev_hash());
+            if let Some(latest) = schema.document_versions(&document_id).last() {
+                if latest != *self.prev_hash() {
+                    Err(Error::NotLatestVersion)?
                 }
             }
-            _ => {
-                Err(Error::TimeServiceError)?
+            document_id
+        };
+
+        create_timestamp(
+            view,
+            &Message::hash(self),
+            self.pub_key(),
+            self.doc_hash(),
+            self.doc_metainfo()
+        )?;
+
+        let mut schema = TimestampingSchema::new(view);
+        {
+            let mut versions = schema.document_versions_mut(&document_id);
+            if versions.is_empty() {
+                versions.push(*self.prev_hash());
             }
+            versions.push(*self.doc_hash());
         }
+        let mut document_ids = schema.document_ids_mut();
+        document_ids.put(self.prev_hash(), document_id);
+        document_ids.put(self.doc_hash(), document_id);
+        Ok(())
     }
 }
 
+/// Implementation of `Transaction` trait for `TxBatch`
+impl Transaction for TxBatch {
+    /// Verifies the internal consistency of the transaction.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
 
+    /// Puts single `Timestamp` for the root of the batch.
+    ///
+    /// Documents of the batch are proven by their `MerklePath`s to the root.
+    fn execute(&self, view: &mut Fork) -> ExecutionResult {
+        if self.leaves_count() == 0 {
+            Err(Error::EmptyBatch)?
+        }
+        create_timestamp(view, &Message::hash(self), self.pub_key(), self.root_hash(), "")
+    }
+}
 
+/// Implementation of `Transaction` trait for `TxDigest`
+impl Transaction for TxDigest {
+    /// Verifies the internal consistency of the transaction.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
 
+    /// Puts new `Timestamp` of the `digest` made with `algorithm`.
+    fn execute(&self, view: &mut Fork) -> ExecutionResult {
+        let algorithm = match HashAlgorithm::from_id(self.algorithm()) {
+            Some(algorithm) => algorithm,
+            None => Err(Error::UnsupportedHashAlgorithm)?,
+        };
+        if self.digest().len() != algorithm.digest_len() {
+            Err(Error::InvalidDigestLength)?
+        }
+        if self.doc_metainfo().len() > MAX_METAINFO_LEN {
+            Err(Error::MetainfoTooLong)?
+        }
+        create_digest_timestamp(
+            view,
+            &Message::hash(self),
+            self.pub_key(),
+            algorithm,
+            self.digest(),
+            self.doc_metainfo()
+        )
+    }
+}
 
+/// Implementation of `Transaction` trait for `TxAttest`
+impl Transaction for TxAttest {
+    /// Verifies the internal consistency of the transaction.
+    fn verify(&self) -> bool {
+        self.verify_signature(self.pub_key())
+    }
 
+    /// Puts the validator attestation into the `Timestamp` by given `data_hash`.
+    ///
+    /// Schema version 0 timestamps are moved to the actual timestamps table
+    /// once attested.
+    fn execute(&self, view: &mut Fork) -> ExecutionResult {
+        let is_validator = Schema::new(&view)
+            .actual_configuration()
+            .validator_keys
+            .iter()
+            .any(|keys| keys.service_key == *self.pub_key());
+        if !is_validator {
+            Err(Error::UnknownValidator)?
+        }
 
+        let mut schema = TimestampingSchema::new(view);
+        let timestamp = match schema.timestamp(self.data_hash()) {
+            Some(timestamp) => timestamp,
+            None => Err(Error::TimestampNotFound)?,
+        };
+        if timestamp.is_attested() {
+            Err(Error::AlreadyAttested)?
+        }
+        if !crypto::verify(self.attestation(), &timestamp.payload().into_bytes(), self.pub_key()) {
+            Err(Error::InvalidAttestation)?
+        }
+
+        let timestamp = timestamp.attest(self.pub_key(), self.attestation());
+        schema.timestamps_mut().put(self.data_hash(), timestamp);
+        schema.legacy_timestamps_mut().remove(self.data_hash());
+        schema.advance_attestation_cursor();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashSet;
+
+    use chrono::Utc;
+    use futures::sync::mpsc;
+    use exonum::blockchain::{Blockchain, ExecutionError, GenesisConfig, Transaction, ValidatorKeys};
+    use exonum::crypto::{self, gen_keypair, hash, Hash, PublicKey, SecretKey};
+    use exonum::node::ApiSender;
+    use exonum::storage::{Fork, MemoryDB, StorageValue};
+    use exonum_time::TimeSchema;
+
+    use blockchain::events::Subscribers;
+    use timestamping::TimestampingService;
+    use timestamping::errors::Error;
+    use timestamping::schema::{LegacyTimestamp, TimestampingSchema};
+    use timestamping::transactions::{Tx, TxAttest, TxNewVersion};
+
+    /// Fork of the blockchain with validators of `service_keys` and current consolidated time
+    fn fork(service_keys: &[PublicKey]) -> Fork {
+        let (service_key, service_secret) = gen_keypair();
+        let mut blockchain = Blockchain::new(
+            Box::new(MemoryDB::new()),
+            vec![Box::new(TimestampingService::new(Subscribers::new()))],
+            service_key,
+            service_secret,
+            ApiSender::new(mpsc::channel(1).0),
+        );
+        let genesis = GenesisConfig::new(service_keys.iter().map(|&service_key| {
+            ValidatorKeys {
+                consensus_key: gen_keypair().0,
+                service_key,
+            }
+        }));
+        blockchain.initialize(genesis).unwrap();
+
+        let mut fork = blockchain.fork();
+        TimeSchema::new(&mut fork).time_mut().set(Utc::now());
+        fork
+    }
+
+    fn create_timestamp(fork: &mut Fork, data_hash: &Hash) {
+        let (public_key, secret_key) = gen_keypair();
+        Tx::new(&public_key, data_hash, &secret_key).execute(fork).unwrap();
+    }
+
+    fn attest(
+        fork: &mut Fork,
+        data_hash: &Hash,
+        keys: &(PublicKey, SecretKey)
+    ) -> Result<(), ExecutionError> {
+        let payload = TimestampingSchema::new(&*fork).timestamp(data_hash).unwrap().payload();
+        let attestation = crypto::sign(&payload.into_bytes(), &keys.1);
+        TxAttest::new(&keys.0, data_hash, &attestation, &keys.1).execute(fork)
+    }
+
+    #[test]
+    fn test_attest() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let data_hash = hash(b"document");
+        create_timestamp(&mut fork, &data_hash);
+
+        attest(&mut fork, &data_hash, &validator).unwrap();
+
+        let schema = TimestampingSchema::new(&fork);
+        let timestamp = schema.timestamps().get(&data_hash).unwrap();
+        assert_eq!(*timestamp.signer(), validator.0);
+        assert!(timestamp.verify_signature());
+        assert_eq!(schema.attestation_cursor(), 1);
+        assert!(schema.pending_attestations(10, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_attest_by_unknown_validator() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let data_hash = hash(b"document");
+        create_timestamp(&mut fork, &data_hash);
+
+        let result = attest(&mut fork, &data_hash, &gen_keypair());
+        assert_eq!(result, Err(Error::UnknownValidator.into()));
+        assert!(!TimestampingSchema::new(&fork).timestamp(&data_hash).unwrap().is_attested());
+    }
+
+    #[test]
+    fn test_attest_twice() {
+        let validators = [gen_keypair(), gen_keypair()];
+        let mut fork = fork(&[validators[0].0, validators[1].0]);
+        let data_hash = hash(b"document");
+        create_timestamp(&mut fork, &data_hash);
+
+        attest(&mut fork, &data_hash, &validators[0]).unwrap();
+        let result = attest(&mut fork, &data_hash, &validators[1]);
+        assert_eq!(result, Err(Error::AlreadyAttested.into()));
+
+        let timestamp = TimestampingSchema::new(&fork).timestamp(&data_hash).unwrap();
+        assert_eq!(*timestamp.signer(), validators[0].0);
+    }
+
+    #[test]
+    fn test_attest_with_invalid_signature() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let data_hash = hash(b"document");
+        create_timestamp(&mut fork, &data_hash);
+
+        let attestation = crypto::sign(data_hash.as_ref(), &validator.1);
+        let tx = TxAttest::new(&validator.0, &data_hash, &attestation, &validator.1);
+        assert_eq!(tx.execute(&mut fork), Err(Error::InvalidAttestation.into()));
+    }
+
+    #[test]
+    fn test_attest_unknown_timestamp() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let data_hash = hash(b"document");
+
+        let attestation = crypto::sign(data_hash.as_ref(), &validator.1);
+        let tx = TxAttest::new(&validator.0, &data_hash, &attestation, &validator.1);
+        assert_eq!(tx.execute(&mut fork), Err(Error::TimestampNotFound.into()));
+    }
+
+    #[test]
+    fn test_legacy_migration() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let data_hash = hash(b"legacy document");
+        let legacy = LegacyTimestamp::new(&hash(b"legacy signature"), 1_522_530_663, &data_hash);
+        TimestampingSchema::new(&mut fork).legacy_timestamps_mut().put(&data_hash, legacy);
+
+        assert_eq!(
+            TimestampingSchema::new(&fork).pending_attestations(10, &HashSet::new()),
+            vec![data_hash]
+        );
+        attest(&mut fork, &data_hash, &validator).unwrap();
+
+        let schema = TimestampingSchema::new(&fork);
+        assert!(!schema.legacy_timestamps().contains(&data_hash));
+        let timestamp = schema.timestamps().get(&data_hash).unwrap();
+        assert_eq!(timestamp.timestamp(), 1_522_530_663);
+        assert_eq!(*timestamp.signer(), validator.0);
+        assert!(timestamp.verify_signature());
+        assert!(schema.pending_attestations(10, &HashSet::new()).is_empty());
+    }
+
+    #[test]
+    fn test_pending_attestations_order() {
+        let validator = gen_keypair();
+        let mut fork = fork(&[validator.0]);
+        let legacy_hash = hash(b"legacy document");
+        let legacy = LegacyTimestamp::new(&hash(b"legacy signature"), 1_522_530_663, &legacy_hash);
+        TimestampingSchema::new(&mut fork).legacy_timestamps_mut().put(&legacy_hash, legacy);
+
+        let mut hashes: Vec<Hash> = (0..5u8).map(|i| hash(&[i])).collect();
+        hashes.sort_by(|a, b| b.cmp(a));
+        for data_hash in &hashes {
+            create_timestamp(&mut fork, data_hash);
+        }
+
+        let none = HashSet::new();
+        let schema = TimestampingSchema::new(&fork);
+        assert_eq!(schema.pending_attestations(3, &none), &hashes[..3]);
+        let mut all = hashes.clone();
+        all.push(legacy_hash);
+        assert_eq!(schema.pending_attestations(10, &none), all);
+
+        let pooled: HashSet<Hash> = hashes[..2].iter().cloned().collect();
+        assert_eq!(schema.pending_attestations(2, &pooled), &hashes[2..4]);
+
+        attest(&mut fork, &hashes[1], &validator).unwrap();
+        assert_eq!(TimestampingSchema::new(&fork).attestation_cursor(), 0);
+        attest(&mut fork, &hashes[0], &validator).unwrap();
+        let schema = TimestampingSchema::new(&fork);
+        assert_eq!(schema.attestation_cursor(), 2);
+        assert_eq!(schema.pending_attestations(1, &none), &hashes[2..3]);
+    }
+
+    fn new_version(
+        fork: &mut Fork,
+        keys: &(PublicKey, SecretKey),
+        data_hash: &Hash,
+        prev_hash: &Hash
+    ) -> Result<(), ExecutionError> {
+        TxNewVersion::new(&keys.0, data_hash, prev_hash, "", &keys.1).execute(fork)
+    }
+
+    #[test]
+    fn test_version_chain() {
+        let author = gen_keypair();
+        let mut fork = fork(&[gen_keypair().0]);
+        let versions: Vec<Hash> = (0..3u8).map(|i| hash(&[i])).collect();
+        Tx::new(&author.0, &versions[0], &author.1).execute(&mut fork).unwrap();
+        new_version(&mut fork, &author, &versions[1], &versions[0]).unwrap();
+        new_version(&mut fork, &author, &versions[2], &versions[1]).unwrap();
+
+        let schema = TimestampingSchema::new(&fork);
+        let document_versions: Vec<Hash> = schema.document_versions(&versions[0]).iter().collect();
+        assert_eq!(document_versions, versions);
+        for version in &versions {
+            assert_eq!(schema.document_id(version), versions[0]);
+            assert_eq!(schema.timestamp(version).unwrap().author(), &author.0);
+        }
+    }
+
+    #[test]
+    fn test_version_by_another_author() {
+        let author = gen_keypair();
+        let mut fork = fork(&[gen_keypair().0]);
+        let first = hash(b"first version");
+        let second = hash(b"second version");
+        Tx::new(&author.0, &first, &author.1).execute(&mut fork).unwrap();
+
+        let result = new_version(&mut fork, &gen_keypair(), &second, &first);
+        assert_eq!(result, Err(Error::NotDocumentAuthor.into()));
+
+        let schema = TimestampingSchema::new(&fork);
+        assert!(schema.timestamp(&second).is_none());
+        assert!(schema.document_versions(&first).is_empty());
+    }
+
+    #[test]
+    fn test_version_of_superseded_version() {
+        let author = gen_keypair();
+        let mut fork = fork(&[gen_keypair().0]);
+        let versions: Vec<Hash> = (0..3u8).map(|i| hash(&[i])).collect();
+        Tx::new(&author.0, &versions[0], &author.1).execute(&mut fork).unwrap();
+        new_version(&mut fork, &author, &versions[1], &versions[0]).unwrap();
+
+        let result = new_version(&mut fork, &author, &versions[2], &versions[0]);
+        assert_eq!(result, Err(Error::NotLatestVersion.into()));
+
+        let schema = TimestampingSchema::new(&fork);
+        assert!(schema.timestamp(&versions[2]).is_none());
+        assert_eq!(schema.document_versions(&versions[0]).len(), 2);
+    }
+
+    #[test]
+    fn test_version_of_unknown_document() {
+        let author = gen_keypair();
+        let mut fork = fork(&[gen_keypair().0]);
+
+        let result = new_version(&mut fork, &author, &hash(b"second"), &hash(b"first"));
+        assert_eq!(result, Err(Error::PreviousVersionNotFound.into()));
+    }
+}
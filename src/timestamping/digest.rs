@@ -0,0 +1,209 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::str::FromStr;
+
+use exonum::crypto::{hash, Hash};
+use sha2::{Digest, Sha256, Sha512};
+use sha3::Sha3_256;
+
+/// Hash algorithm of the timestamped document digest
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+#[repr(u8)]
+pub enum HashAlgorithm {
+    Sha256 = 0,
+    Sha512 = 1,
+    Sha3_256 = 2,
+}
+
+impl HashAlgorithm {
+    /// Algorithm by its identifier stored in `Timestamp` and transactions
+    pub fn from_id(id: u8) -> Option<HashAlgorithm> {
+        match id {
+            0 => Some(HashAlgorithm::Sha256),
+            1 => Some(HashAlgorithm::Sha512),
+            2 => Some(HashAlgorithm::Sha3_256),
+            _ => None,
+        }
+    }
+
+    pub fn id(&self) -> u8 {
+        *self as u8
+    }
+
+    /// Length of the digest in bytes
+    pub fn digest_len(&self) -> usize {
+        match *self {
+            HashAlgorithm::Sha256 | HashAlgorithm::Sha3_256 => 32,
+            HashAlgorithm::Sha512 => 64,
+        }
+    }
+
+    /// Computes digest of the `data`, the same as `DigestStream` does.
+    pub fn digest(&self, data: &[u8]) -> Vec<u8> {
+        let mut stream = DigestStream::new(*self);
+        stream.update(data);
+        stream.finish()
+    }
+
+    /// Key of the timestamp of `digest` in the timestamps table
+    ///
+    /// SHA-256 digests are used as keys as is, so timestamps made before algorithms
+    /// were introduced keep their keys. Other digests are namespaced by the algorithm id.
+    pub fn storage_key(&self, digest: &[u8]) -> Hash {
+        match *self {
+            HashAlgorithm::Sha256 => Hash::from_slice(digest).unwrap(),
+            _ => hash(&[&[self.id()][..], digest].concat()),
+        }
+    }
+}
+
+impl Default for HashAlgorithm {
+    fn default() -> HashAlgorithm {
+        HashAlgorithm::Sha256
+    }
+}
+
+impl FromStr for HashAlgorithm {
+    type Err = String;
+
+    /// Parses the algorithm name the same way it is serialized, e.g. `sha3_256`.
+    fn from_str(name: &str) -> Result<HashAlgorithm, String> {
+        match name {
+            "sha256" => Ok(HashAlgorithm::Sha256),
+            "sha512" => Ok(HashAlgorithm::Sha512),
+            "sha3_256" => Ok(HashAlgorithm::Sha3_256),
+            _ => Err(format!("Unsupported hash algorithm `{}`", name)),
+        }
+    }
+}
+
+/// Incremental hasher of the `HashAlgorithm`, e.g. for the streamed data
+pub enum DigestStream {
+    Sha256(Sha256),
+    Sha512(Sha512),
+    Sha3_256(Sha3_256),
+}
+
+impl DigestStream {
+    pub fn new(algorithm: HashAlgorithm) -> DigestStream {
+        match algorithm {
+            HashAlgorithm::Sha256 => DigestStream::Sha256(Sha256::default()),
+            HashAlgorithm::Sha512 => DigestStream::Sha512(Sha512::default()),
+            HashAlgorithm::Sha3_256 => DigestStream::Sha3_256(Sha3_256::default()),
+        }
+    }
+
+    /// Processes the next `chunk` of data.
+    pub fn update(&mut self, chunk: &[u8]) {
+        match *self {
+            DigestStream::Sha256(ref mut hasher) => hasher.input(chunk),
+            DigestStream::Sha512(ref mut hasher) => hasher.input(chunk),
+            DigestStream::Sha3_256(ref mut hasher) => hasher.input(chunk),
+        }
+    }
+
+    /// Digest of all the processed data
+    pub fn finish(self) -> Vec<u8> {
+        match self {
+            DigestStream::Sha256(hasher) => hasher.result().to_vec(),
+            DigestStream::Sha512(hasher) => hasher.result().to_vec(),
+            DigestStream::Sha3_256(hasher) => hasher.result().to_vec(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::{hash, Hash};
+    use exonum::encoding::serialize::FromHex;
+
+    use super::{DigestStream, HashAlgorithm};
+
+    const ALGORITHMS: &[HashAlgorithm] =
+        &[HashAlgorithm::Sha256, HashAlgorithm::Sha512, HashAlgorithm::Sha3_256];
+
+    #[test]
+    fn test_digest() {
+        let vectors = [
+            (
+                HashAlgorithm::Sha256,
+                "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad",
+            ),
+            (
+                HashAlgorithm::Sha512,
+                concat!(
+                    "ddaf35a193617abacc417349ae20413112e6fa4e89a97ea20a9eeee64b55d39a",
+                    "2192992a274fc1a836ba3c23a3feebbd454d4423643ce80e2a9ac94fa54ca49f",
+                ),
+            ),
+            (
+                HashAlgorithm::Sha3_256,
+                "3a985da74fe225b2045c172d6bd390bd855f086e3e9d525b46bfe24511431532",
+            ),
+        ];
+        for &(algorithm, expected) in &vectors {
+            let digest = algorithm.digest(b"abc");
+            assert_eq!(digest, Vec::<u8>::from_hex(expected).unwrap(), "{:?}", algorithm);
+            assert_eq!(digest.len(), algorithm.digest_len());
+        }
+        assert_eq!(HashAlgorithm::Sha256.digest(b"abc"), hash(b"abc").as_ref().to_vec());
+    }
+
+    #[test]
+    fn test_stream_matches_digest() {
+        let data: Vec<u8> = (0..1000u32).map(|i| (i % 251) as u8).collect();
+        for &algorithm in ALGORITHMS {
+            for &chunk_len in &[1, 7, 64, 1000] {
+                let mut stream = DigestStream::new(algorithm);
+                for chunk in data.chunks(chunk_len) {
+                    stream.update(chunk);
+                }
+                assert_eq!(stream.finish(), algorithm.digest(&data), "{:?}", algorithm);
+            }
+            assert_eq!(DigestStream::new(algorithm).finish(), algorithm.digest(&[]));
+        }
+    }
+
+    #[test]
+    fn test_storage_key() {
+        let digest = hash(b"document");
+        assert_eq!(HashAlgorithm::Sha256.storage_key(digest.as_ref()), digest);
+
+        // 32 bytes digests of the other algorithms don't take the raw SHA-256 key
+        let sha3_key = HashAlgorithm::Sha3_256.storage_key(digest.as_ref());
+        assert_ne!(sha3_key, digest);
+        let namespaced = [&[HashAlgorithm::Sha3_256.id()][..], digest.as_ref()].concat();
+        assert_eq!(sha3_key, hash(&namespaced));
+
+        let keys: Vec<Hash> = ALGORITHMS
+            .iter()
+            .map(|algorithm| algorithm.storage_key(&algorithm.digest(b"document")))
+            .collect();
+        assert_eq!(keys[0], digest);
+        assert_ne!(keys[1], keys[0]);
+        assert_ne!(keys[2], keys[0]);
+        assert_ne!(keys[2], keys[1]);
+    }
+
+    #[test]
+    fn test_ids() {
+        for &algorithm in ALGORITHMS {
+            assert_eq!(HashAlgorithm::from_id(algorithm.id()), Some(algorithm));
+        }
+        assert_eq!(HashAlgorithm::from_id(3), None);
+    }
+}
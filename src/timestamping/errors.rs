@@ -27,6 +27,61 @@ pub enum Error {
     /// Can be emitted in the case of consolidated time is not received.
     #[fail(display = "Couldn't retrieve current time")]
     TimeServiceError = 1,
+
+    /// Timestamp not found.
+    /// Can be emitted by `TxAttest`.
+    #[fail(display = "Timestamp not found")]
+    TimestampNotFound = 2,
+
+    /// Timestamp is already attested.
+    /// Can be emitted by `TxAttest`.
+    #[fail(display = "Timestamp is already attested")]
+    AlreadyAttested = 3,
+
+    /// Attestation is made by the key which doesn't belong to any validator.
+    /// Can be emitted by `TxAttest`.
+    #[fail(display = "Attestation is made by unknown validator")]
+    UnknownValidator = 4,
+
+    /// Attestation signature doesn't match the timestamp.
+    /// Can be emitted by `TxAttest`.
+    #[fail(display = "Invalid attestation signature")]
+    InvalidAttestation = 5,
+
+    /// Document metainfo exceeds `MAX_METAINFO_LEN`.
+    /// Can be emitted by `TxWithMetainfo`.
+    #[fail(display = "Document metainfo is too long")]
+    MetainfoTooLong = 6,
+
+    /// Previous version of the document not found.
+    /// Can be emitted by `TxNewVersion`.
+    #[fail(display = "Previous version not found")]
+    PreviousVersionNotFound = 7,
+
+    /// Previous version of the document is made by another author.
+    /// Can be emitted by `TxNewVersion`.
+    #[fail(display = "Previous version is made by another author")]
+    NotDocumentAuthor = 8,
+
+    /// Previous version of the document is already superseded.
+    /// Can be emitted by `TxNewVersion`.
+    #[fail(display = "Previous version is not the latest one")]
+    NotLatestVersion = 9,
+
+    /// Merkle batch doesn't contain documents.
+    /// Can be emitted by `TxBatch`.
+    #[fail(display = "Batch is empty")]
+    EmptyBatch = 10,
+
+    /// Hash algorithm id is unknown.
+    /// Can be emitted by `TxDigest`.
+    #[fail(display = "Unsupported hash algorithm")]
+    UnsupportedHashAlgorithm = 11,
+
+    /// Digest length doesn't match the hash algorithm.
+    /// Can be emitted by `TxDigest`.
+    #[fail(display = "Invalid digest length")]
+    InvalidDigestLength = 12,
 }
 
 impl From<Error> for ExecutionError {
@@ -0,0 +1,180 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use exonum::crypto::{Hash, hash};
+
+/// Prefix of the leaf hash preimage
+const LEAF_PREFIX: u8 = 0;
+/// Prefix of the node hash preimage
+const NODE_PREFIX: u8 = 1;
+
+/// Hash of the tree leaf for the `document` hash
+pub fn leaf_hash(document: &Hash) -> Hash {
+    let mut bytes = vec![LEAF_PREFIX];
+    bytes.extend_from_slice(document.as_ref());
+    hash(&bytes)
+}
+
+/// Hash of the tree node with `left` and `right` children
+pub fn node_hash(left: &Hash, right: &Hash) -> Hash {
+    let mut bytes = vec![NODE_PREFIX];
+    bytes.extend_from_slice(left.as_ref());
+    bytes.extend_from_slice(right.as_ref());
+    hash(&bytes)
+}
+
+/// Sibling of the node on the way from the leaf to the root
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(rename_all = "snake_case")]
+pub enum PathElement {
+    /// sibling is the left child
+    Left(Hash),
+    /// sibling is the right child
+    Right(Hash),
+}
+
+/// Inclusion path of the document into the `MerkleBatch`
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+pub struct MerklePath {
+    pub siblings: Vec<PathElement>,
+}
+
+impl MerklePath {
+    /// Computes the tree root for the `document` hash following the path.
+    pub fn root(&self, document: &Hash) -> Hash {
+        self.siblings.iter().fold(leaf_hash(document), |node, sibling| match *sibling {
+            PathElement::Left(ref left) => node_hash(left, &node),
+            PathElement::Right(ref right) => node_hash(&node, right),
+        })
+    }
+
+    /// Checks the `document` hash is included into the tree with the given `root`.
+    pub fn verify(&self, document: &Hash, root: &Hash) -> bool {
+        self.root(document) == *root
+    }
+}
+
+/// Client-side Merkle tree of document hashes
+///
+/// The tree root is timestamped by the single `TxBatch`, the inclusion of each document
+/// is proven by its `MerklePath`. The last node of the odd-sized level is promoted
+/// to the next level as is.
+#[derive(Clone, Debug)]
+pub struct MerkleBatch {
+    levels: Vec<Vec<Hash>>,
+}
+
+impl MerkleBatch {
+    /// Builds the tree of `documents` hashes, `None` in the case of `documents` is empty.
+    pub fn new(documents: &[Hash]) -> Option<MerkleBatch> {
+        if documents.is_empty() {
+            return None;
+        }
+
+        let mut levels = vec![documents.iter().map(leaf_hash).collect::<Vec<Hash>>()];
+        while levels.last().unwrap().len() > 1 {
+            let next = levels
+                .last()
+                .unwrap()
+                .chunks(2)
+                .map(|pair| if pair.len() == 2 {
+                    node_hash(&pair[0], &pair[1])
+                } else {
+                    pair[0]
+                })
+                .collect();
+            levels.push(next);
+        }
+        Some(MerkleBatch { levels })
+    }
+
+    /// Number of documents in the tree
+    pub fn len(&self) -> usize {
+        self.levels[0].len()
+    }
+
+    /// Always `false`, the tree of no documents isn't built
+    pub fn is_empty(&self) -> bool {
+        self.levels[0].is_empty()
+    }
+
+    /// Tree root to be timestamped
+    pub fn root(&self) -> Hash {
+        self.levels.last().unwrap()[0]
+    }
+
+    /// Inclusion path of the document with the given `index`
+    pub fn path(&self, index: usize) -> Option<MerklePath> {
+        if index >= self.len() {
+            return None;
+        }
+
+        let mut index = index;
+        let mut siblings = Vec::new();
+        for level in &self.levels[..self.levels.len() - 1] {
+            let sibling = index ^ 1;
+            if sibling < level.len() {
+                siblings.push(if index % 2 == 0 {
+                    PathElement::Right(level[sibling])
+                } else {
+                    PathElement::Left(level[sibling])
+                });
+            }
+            index /= 2;
+        }
+        Some(MerklePath { siblings })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::{CryptoHash, Hash};
+
+    use super::MerkleBatch;
+
+    fn documents(count: u64) -> Vec<Hash> {
+        (0..count).map(|i| i.hash()).collect()
+    }
+
+    #[test]
+    fn test_empty_batch() {
+        assert!(MerkleBatch::new(&[]).is_none());
+    }
+
+    #[test]
+    fn test_paths_verify() {
+        for &count in &[1, 2, 3, 5, 8] {
+            let documents = documents(count);
+            let batch = MerkleBatch::new(&documents).unwrap();
+            assert_eq!(batch.len(), documents.len());
+            assert!(!batch.is_empty());
+
+            let root = batch.root();
+            for (index, document) in documents.iter().enumerate() {
+                let path = batch.path(index).unwrap();
+                assert!(path.verify(document, &root), "size {}, index {}", count, index);
+            }
+            assert!(batch.path(documents.len()).is_none());
+        }
+    }
+
+    #[test]
+    fn test_path_of_another_document() {
+        let documents = documents(5);
+        let batch = MerkleBatch::new(&documents).unwrap();
+        let path = batch.path(4).unwrap();
+        assert!(!path.verify(&documents[3], &batch.root()));
+    }
+}
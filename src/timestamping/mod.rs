@@ -15,32 +15,89 @@
 
 pub mod schema;
 pub mod transactions;
+pub mod proof;
+pub mod merkle;
+pub mod status;
+pub mod ots;
+pub mod digest;
 mod errors;
 mod contracts;
 mod api;
+mod rfc3161;
 
-use exonum::blockchain::{Service, Transaction, ApiContext, TransactionSet};
+use std::collections::HashSet;
+
+use exonum::blockchain::{Schema, Service, ServiceContext, Transaction, ApiContext, TransactionSet};
 use exonum::helpers::fabric::{ServiceFactory, Context};
 use exonum::messages::RawTransaction;
+use exonum::node::TransactionSend;
 use exonum::storage::Snapshot;
-use exonum::crypto::Hash;
+use exonum::crypto::{self, Hash};
 use exonum::encoding;
+use exonum::storage::StorageValue;
 use exonum::api::Api;
 use iron::Handler;
 use router::Router;
 
-use timestamping::schema::TimestampingSchema;
-use timestamping::transactions::TimestampingTransactions;
+use blockchain::events::{Event, Subscribers};
+use timestamping::schema::{Timestamp, TimestampingSchema, MAX_ATTESTATIONS_PER_BLOCK};
+use timestamping::transactions::{TimestampingTransactions, TxAttest};
+use timestamping::status::Waiters;
 use timestamping::api::TimestampingApi;
+use timestamping::rfc3161::Rfc3161Api;
 
 pub const SERVICE_ID: u16 = 42;
 
-pub struct TimestampingService;
+/// Data hashes of timestamps which `TxAttest` is already waiting in the pool
+fn pooled_attestations(snapshot: &Snapshot) -> HashSet<Hash> {
+    let schema = Schema::new(snapshot);
+    let transactions = schema.transactions();
+    schema
+        .transactions_pool()
+        .iter()
+        .filter_map(|tx_hash| transactions.get(&tx_hash))
+        .filter(|raw| raw.service_id() == SERVICE_ID)
+        .filter_map(|raw| match TimestampingTransactions::tx_from_raw(raw) {
+            Ok(TimestampingTransactions::TxAttest(tx)) => Some(*tx.data_hash()),
+            _ => None,
+        })
+        .collect()
+}
+
+pub struct TimestampingService {
+    subscribers: Subscribers,
+}
 
 /// `TimestampingService` instance which provides Api to make and validate timestamps of data
 impl TimestampingService {
-    pub fn default() -> TimestampingService {
-        TimestampingService
+    /// Constructs the service pushing created timestamps to `subscribers`,
+    /// which should be shared with `BlockchainService` serving the subscriptions.
+    pub fn new(subscribers: Subscribers) -> TimestampingService {
+        TimestampingService { subscribers }
+    }
+
+    /// Pushes timestamps created in the committed block to the subscribers.
+    fn notify_subscribers(&self, context: &ServiceContext) {
+        if self.subscribers.is_empty() {
+            return;
+        }
+
+        // New timestamps are at the end of the time-ordered list
+        let snapshot = context.snapshot();
+        let height = Schema::new(snapshot).height().0;
+        let schema = TimestampingSchema::new(snapshot);
+        let by_time = schema.timestamps_by_time();
+        let timestamps = schema.timestamps();
+        let mut created: Vec<Timestamp> = (0..by_time.len())
+            .rev()
+            .filter_map(|position| by_time.get(position))
+            .filter_map(|data_hash| timestamps.get(&data_hash))
+            .take_while(|timestamp| timestamp.height() == height)
+            .collect();
+        created.reverse();
+
+        let events: Vec<Event> = created.into_iter().map(Event::Timestamp).collect();
+        self.subscribers.notify(&events);
     }
 }
 
@@ -67,20 +124,66 @@ impl Service for TimestampingService {
         schema.state_hash()
     }
 
+    // Pushes created timestamps to the subscribers and attests timestamps by the service key
+    // of the validator proposed the committed block.
+    // Attestations are sent as `TxAttest` transactions, so all nodes store the same signature.
+    // Timestamps are attested in the order of creation, the ones already attested by
+    // transactions in the pool are skipped.
+    fn handle_commit(&self, context: &ServiceContext) {
+        self.notify_subscribers(context);
+
+        let validator_id = match context.validator_id() {
+            Some(validator_id) => validator_id,
+            None => return,
+        };
+        let snapshot = context.snapshot();
+        if Schema::new(snapshot).last_block().proposer_id() != validator_id {
+            return;
+        }
+
+        let schema = TimestampingSchema::new(snapshot);
+        let pooled = pooled_attestations(snapshot);
+        for data_hash in schema.pending_attestations(MAX_ATTESTATIONS_PER_BLOCK, &pooled) {
+            let timestamp = schema.timestamp(&data_hash).unwrap();
+            let attestation = crypto::sign(&timestamp.payload().into_bytes(), context.secret_key());
+            let tx = TxAttest::new(
+                context.public_key(),
+                &data_hash,
+                &attestation,
+                context.secret_key()
+            );
+            if let Err(e) = context.transaction_sender().send(Box::new(tx)) {
+                error!("Couldn't send attestation of {:?}: {}", data_hash, e);
+            }
+        }
+    }
+
     // Create a REST `Handler` to process web requests to the node.
     fn public_api_handler(&self, ctx: &ApiContext) -> Option<Box<Handler>> {
         let mut router = Router::new();
-        let api = TimestampingApi::new(ctx);
+        let waiters = Waiters::new();
+        let api = TimestampingApi::new(ctx, waiters.clone());
         api.wire(&mut router);
+        let rfc3161_api = Rfc3161Api::new(ctx, waiters);
+        rfc3161_api.wire(&mut router);
         Some(Box::new(router))
     }
 }
 
 #[derive(Debug)]
-pub struct TimestampingServiceFactory;
+pub struct TimestampingServiceFactory {
+    subscribers: Subscribers,
+}
+
+impl TimestampingServiceFactory {
+    /// Constructs the factory of `TimestampingService` pushing events to `subscribers`.
+    pub fn new(subscribers: Subscribers) -> TimestampingServiceFactory {
+        TimestampingServiceFactory { subscribers }
+    }
+}
 
 impl ServiceFactory for TimestampingServiceFactory {
     fn make_service(&mut self, _: &Context) -> Box<Service> {
-        Box::new(TimestampingService::default())
+        Box::new(TimestampingService::new(self.subscribers.clone()))
     }
 }
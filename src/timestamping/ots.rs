@@ -0,0 +1,216 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use exonum::crypto::{hash, CryptoHash, Hash, SIGNATURE_LENGTH};
+use exonum::storage::{Snapshot, StorageValue};
+use serde_json;
+
+use super::schema::TimestampingSchema;
+use super::proof::TimestampProof;
+use super::digest::HashAlgorithm;
+
+/// Header of the OpenTimestamps detached proof file
+pub const OTS_MAGIC: &[u8] = b"\x00OpenTimestamps\x00\x00Proof\x00\xbf\x89\xe2\xe8\x84\xe8\x92\x94";
+/// Version of the OpenTimestamps proof format
+pub const OTS_VERSION: u64 = 1;
+
+pub const OP_SHA256: u8 = 0x08;
+pub const OP_APPEND: u8 = 0xf0;
+pub const OP_PREPEND: u8 = 0xf1;
+pub const TAG_ATTESTATION: u8 = 0x00;
+pub const TAG_FORK: u8 = 0xff;
+
+/// Tag of `ExonumAttestation`, unknown to the OpenTimestamps tooling,
+/// so it is displayed as the pending attestation of the unknown kind.
+pub const EXONUM_ATTESTATION_TAG: [u8; 8] = *b"EXONUMTS";
+
+/// Maximum length of the attestation payload accepted by the OpenTimestamps tooling
+pub const MAX_PAYLOAD_LEN: usize = 8192;
+
+/// Offset of `data_hash` in the `Timestamp` serialized representation,
+/// it follows `signature: &Signature` and `timestamp: i64` fields.
+const DATA_HASH_OFFSET: usize = SIGNATURE_LENGTH + 8;
+
+/// Reasons the timestamp can't be exported as `.ots` proof
+#[derive(Debug, Fail)]
+pub enum OtsError {
+    /// Timestamp isn't stored in the timestamps table.
+    #[fail(display = "Timestamp not found")]
+    TimestampNotFound,
+
+    /// OpenTimestamps has no file hash of the timestamp algorithm.
+    #[fail(display = "Only SHA-256 timestamps can be exported")]
+    UnsupportedAlgorithm,
+
+    /// Attestation payload exceeds `MAX_PAYLOAD_LEN`.
+    #[fail(display = "Attestation payload of {} bytes exceeds {} bytes limit", _0, _1)]
+    PayloadTooLarge(usize, usize),
+
+    /// Serialized `Timestamp` has no data hash at `DATA_HASH_OFFSET`.
+    #[fail(display = "Unexpected `Timestamp` layout")]
+    UnexpectedLayout,
+}
+
+/// Commitment operation of the OpenTimestamps proof
+#[derive(Clone, Debug, PartialEq)]
+pub enum OtsOp {
+    Sha256,
+    Append(Vec<u8>),
+    Prepend(Vec<u8>),
+}
+
+impl OtsOp {
+    /// Applies the operation to the `message`.
+    pub fn apply(&self, message: &[u8]) -> Vec<u8> {
+        match *self {
+            OtsOp::Sha256 => hash(message).as_ref().to_vec(),
+            OtsOp::Append(ref suffix) => [message, &suffix[..]].concat(),
+            OtsOp::Prepend(ref prefix) => [&prefix[..], message].concat(),
+        }
+    }
+}
+
+/// Attestation of the commitment by the block of the timestamping blockchain
+///
+/// The commitment is the hash of the `Timestamp` stored in the timestamps table,
+/// `proof` binds it to the block with `block_hash` at `height`.
+///
+/// OpenTimestamps tooling limits attestation payload to `MAX_PAYLOAD_LEN`, the proof of the
+/// network with many validators or the large timestamps table may not fit into it.
+#[derive(Debug)]
+pub struct ExonumAttestation {
+    pub height: u64,
+    pub block_hash: Hash,
+    pub proof: TimestampProof,
+}
+
+impl ExonumAttestation {
+    /// Attestation payload: `varuint(height) || block_hash || proof JSON`
+    pub fn payload(&self) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        write_varuint(&mut bytes, self.height);
+        bytes.extend_from_slice(self.block_hash.as_ref());
+        bytes.extend(serde_json::to_vec(&self.proof).unwrap());
+        bytes
+    }
+}
+
+/// OpenTimestamps detached proof of the timestamp
+///
+/// Operations lead from the document hash to the commitment attested by `attestation`,
+/// only the linear proofs without forks are produced.
+#[derive(Debug)]
+pub struct OtsProof {
+    pub file_hash: Hash,
+    pub ops: Vec<OtsOp>,
+    pub attestation: ExonumAttestation,
+}
+
+impl OtsProof {
+    /// Builds proof of the committed timestamp for the given `data_hash` against the latest
+    /// committed block.
+    ///
+    /// OpenTimestamps has no SHA-512 and SHA3-256 file hashes, so only SHA-256 timestamps
+    /// are exported. The proof is not built if its attestation payload doesn't fit
+    /// into `MAX_PAYLOAD_LEN`.
+    pub fn new(snapshot: &Snapshot, data_hash: &Hash) -> Result<OtsProof, OtsError> {
+        let timestamp = TimestampingSchema::new(snapshot)
+            .timestamps()
+            .get(data_hash)
+            .ok_or(OtsError::TimestampNotFound)?;
+        if timestamp.hash_algorithm() != HashAlgorithm::Sha256 {
+            return Err(OtsError::UnsupportedAlgorithm);
+        }
+        let bytes = timestamp.into_bytes();
+        let data_hash_end = DATA_HASH_OFFSET + data_hash.as_ref().len();
+        if bytes.get(DATA_HASH_OFFSET..data_hash_end) != Some(data_hash.as_ref()) {
+            return Err(OtsError::UnexpectedLayout);
+        }
+        let ops = vec![
+            OtsOp::Prepend(bytes[..DATA_HASH_OFFSET].to_vec()),
+            OtsOp::Append(bytes[data_hash_end..].to_vec()),
+            OtsOp::Sha256,
+        ];
+
+        let proof = TimestampProof::new(snapshot, data_hash);
+        let block = proof.block_proof.block.clone();
+        let attestation = ExonumAttestation {
+            height: block.height().0,
+            block_hash: block.hash(),
+            proof,
+        };
+
+        let payload_len = attestation.payload().len();
+        if payload_len > MAX_PAYLOAD_LEN {
+            return Err(OtsError::PayloadTooLarge(payload_len, MAX_PAYLOAD_LEN));
+        }
+
+        Ok(OtsProof {
+            file_hash: *data_hash,
+            ops,
+            attestation,
+        })
+    }
+
+    /// Result of `ops` applied to the `file_hash`
+    pub fn commitment(&self) -> Vec<u8> {
+        self.ops
+            .iter()
+            .fold(self.file_hash.as_ref().to_vec(), |message, op| op.apply(&message))
+    }
+
+    /// Serializes the proof into the `.ots` file format.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = OTS_MAGIC.to_vec();
+        write_varuint(&mut bytes, OTS_VERSION);
+        bytes.push(OP_SHA256);
+        bytes.extend_from_slice(self.file_hash.as_ref());
+
+        for op in &self.ops {
+            match *op {
+                OtsOp::Sha256 => bytes.push(OP_SHA256),
+                OtsOp::Append(ref suffix) => {
+                    bytes.push(OP_APPEND);
+                    write_varbytes(&mut bytes, suffix);
+                }
+                OtsOp::Prepend(ref prefix) => {
+                    bytes.push(OP_PREPEND);
+                    write_varbytes(&mut bytes, prefix);
+                }
+            }
+        }
+
+        bytes.push(TAG_ATTESTATION);
+        bytes.extend_from_slice(&EXONUM_ATTESTATION_TAG);
+        write_varbytes(&mut bytes, &self.attestation.payload());
+        bytes
+    }
+}
+
+/// Writes `value` as the OpenTimestamps variable length unsigned integer.
+pub fn write_varuint(bytes: &mut Vec<u8>, value: u64) {
+    let mut value = value;
+    while value >= 0x80 {
+        bytes.push((value & 0x7f) as u8 | 0x80);
+        value >>= 7;
+    }
+    bytes.push(value as u8);
+}
+
+/// Writes length prefixed `value`.
+pub fn write_varbytes(bytes: &mut Vec<u8>, value: &[u8]) {
+    write_varuint(bytes, value.len() as u64);
+    bytes.extend_from_slice(value);
+}
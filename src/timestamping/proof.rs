@@ -0,0 +1,64 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use exonum::blockchain::{BlockProof, Schema};
+use exonum::crypto::Hash;
+use exonum::storage::{MapProof, Snapshot};
+
+use super::schema::{Timestamp, TimestampingSchema};
+use super::SERVICE_ID;
+
+/// Index of the timestamps table in the `TimestampingService` state hash
+pub const TIMESTAMPS_TABLE_INDEX: usize = 0;
+
+/// Proof of `Timestamp` existence which can be checked without trusting the node
+///
+/// The proof consists of three parts:
+///  - the latest committed block with the precommits of validators;
+///  - the proof from the block `state_hash` to the root of the timestamps table;
+///  - the proof from the root of the timestamps table to the `Timestamp` itself.
+#[derive(Debug, Serialize, Deserialize)]
+pub struct TimestampProof {
+    /// Block header and precommits
+    pub block_proof: BlockProof,
+    /// `state_hash` -> timestamps table root
+    pub to_table: MapProof<Hash, Hash>,
+    /// timestamps table root -> `Timestamp`
+    pub to_timestamp: MapProof<Hash, Timestamp>,
+}
+
+impl TimestampProof {
+    /// Builds proof for the given `data_hash` against the latest committed block.
+    ///
+    /// Only the timestamps table is proven, so the proof of the schema version 0 record
+    /// which is not migrated yet is the proof of absence.
+    pub fn new(snapshot: &Snapshot, data_hash: &Hash) -> TimestampProof {
+        let general_schema = Schema::new(snapshot);
+        let block_proof = general_schema
+            .block_and_precommits(general_schema.height())
+            .unwrap();
+        let to_table: MapProof<Hash, Hash> =
+            general_schema.get_proof_to_service_table(SERVICE_ID, TIMESTAMPS_TABLE_INDEX);
+
+        let schema = TimestampingSchema::new(snapshot);
+        let to_timestamp: MapProof<Hash, Timestamp> = schema.timestamps().get_proof(*data_hash);
+
+        TimestampProof {
+            block_proof,
+            to_table,
+            to_timestamp,
+        }
+    }
+}
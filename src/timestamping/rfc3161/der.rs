@@ -0,0 +1,452 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use chrono::{DateTime, Utc};
+
+pub const TAG_BOOLEAN: u8 = 0x01;
+pub const TAG_INTEGER: u8 = 0x02;
+pub const TAG_BIT_STRING: u8 = 0x03;
+pub const TAG_OCTET_STRING: u8 = 0x04;
+pub const TAG_NULL: u8 = 0x05;
+pub const TAG_OID: u8 = 0x06;
+pub const TAG_UTF8_STRING: u8 = 0x0c;
+pub const TAG_GENERALIZED_TIME: u8 = 0x18;
+pub const TAG_SEQUENCE: u8 = 0x30;
+pub const TAG_SET: u8 = 0x31;
+
+/// Tag of the context-specific constructed value `[number]`
+pub fn context_tag(number: u8) -> u8 {
+    0xa0 | number
+}
+
+/// DER decoding errors
+#[derive(Debug, Fail)]
+pub enum DerError {
+    #[fail(display = "Unexpected end of DER data")]
+    UnexpectedEnd,
+
+    #[fail(display = "Unexpected DER tag {:#04x}, expected {:#04x}", _0, _1)]
+    UnexpectedTag(u8, u8),
+
+    #[fail(display = "Unsupported DER length")]
+    UnsupportedLength,
+
+    #[fail(display = "Invalid DER value")]
+    InvalidValue,
+}
+
+/// Sequential reader of DER encoded values
+pub struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    pub fn new(data: &'a [u8]) -> Reader<'a> {
+        Reader { data }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.data.is_empty()
+    }
+
+    /// Tag of the next value
+    pub fn peek_tag(&self) -> Option<u8> {
+        self.data.first().cloned()
+    }
+
+    /// Reads the next value, returns its tag, full encoding and content.
+    pub fn read_any(&mut self) -> Result<(u8, &'a [u8], &'a [u8]), DerError> {
+        if self.data.len() < 2 {
+            return Err(DerError::UnexpectedEnd);
+        }
+        let tag = self.data[0];
+        let (len, header_len) = match self.data[1] {
+            len if len < 0x80 => (len as usize, 2),
+            0x80 => return Err(DerError::UnsupportedLength),
+            len => {
+                let octets = (len & 0x7f) as usize;
+                if octets > 4 {
+                    return Err(DerError::UnsupportedLength);
+                }
+                if self.data.len() < 2 + octets {
+                    return Err(DerError::UnexpectedEnd);
+                }
+                let len = self.data[2..2 + octets]
+                    .iter()
+                    .fold(0usize, |len, &octet| (len << 8) | octet as usize);
+                // DER requires the minimal length encoding.
+                if self.data[2] == 0 || len < 0x80 {
+                    return Err(DerError::UnsupportedLength);
+                }
+                (len, 2 + octets)
+            }
+        };
+        match header_len.checked_add(len) {
+            Some(total) if total <= self.data.len() => {}
+            _ => return Err(DerError::UnexpectedEnd),
+        }
+
+        let raw = &self.data[..header_len + len];
+        let content = &self.data[header_len..header_len + len];
+        self.data = &self.data[header_len + len..];
+        Ok((tag, raw, content))
+    }
+
+    /// Reads content of the next value with the given `tag`.
+    pub fn read(&mut self, tag: u8) -> Result<&'a [u8], DerError> {
+        self.expect_tag(tag)?;
+        self.read_any().map(|(_, _, content)| content)
+    }
+
+    /// Reads full encoding of the next value with the given `tag`.
+    pub fn read_raw(&mut self, tag: u8) -> Result<&'a [u8], DerError> {
+        self.expect_tag(tag)?;
+        self.read_any().map(|(_, raw, _)| raw)
+    }
+
+    /// Reads content of the next value in the case of it has the given `tag`.
+    pub fn read_optional(&mut self, tag: u8) -> Result<Option<&'a [u8]>, DerError> {
+        if self.peek_tag() == Some(tag) {
+            self.read(tag).map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+
+    fn expect_tag(&self, tag: u8) -> Result<(), DerError> {
+        match self.peek_tag() {
+            Some(actual) if actual == tag => Ok(()),
+            Some(actual) => Err(DerError::UnexpectedTag(actual, tag)),
+            None => Err(DerError::UnexpectedEnd),
+        }
+    }
+}
+
+/// Decodes non-negative INTEGER content which fits into `u64`.
+pub fn parse_u64(content: &[u8]) -> Result<u64, DerError> {
+    if content.is_empty() || content[0] & 0x80 != 0 {
+        return Err(DerError::InvalidValue);
+    }
+    let content = if content.len() > 1 && content[0] == 0 { &content[1..] } else { content };
+    if content.len() > 8 {
+        return Err(DerError::InvalidValue);
+    }
+    Ok(content.iter().fold(0u64, |value, &octet| (value << 8) | octet as u64))
+}
+
+/// Decodes OBJECT IDENTIFIER content into the list of arcs.
+pub fn parse_oid(content: &[u8]) -> Result<Vec<u64>, DerError> {
+    let mut arcs = Vec::new();
+    let mut value = 0u64;
+    for (index, &octet) in content.iter().enumerate() {
+        if value > (u64::max_value() >> 7) {
+            return Err(DerError::InvalidValue);
+        }
+        // Subidentifiers are encoded without leading zero octets.
+        if value == 0 && octet == 0x80 {
+            return Err(DerError::InvalidValue);
+        }
+        value = (value << 7) | (octet & 0x7f) as u64;
+        if octet & 0x80 == 0 {
+            if arcs.is_empty() {
+                let first = (value / 40).min(2);
+                arcs.push(first);
+                arcs.push(value - first * 40);
+            } else {
+                arcs.push(value);
+            }
+            value = 0;
+        } else if index == content.len() - 1 {
+            return Err(DerError::InvalidValue);
+        }
+    }
+    if arcs.is_empty() {
+        return Err(DerError::InvalidValue);
+    }
+    Ok(arcs)
+}
+
+/// Encodes value with the given `tag` and `content`.
+pub fn encode(tag: u8, content: &[u8]) -> Vec<u8> {
+    let mut bytes = vec![tag];
+    let len = content.len();
+    if len < 0x80 {
+        bytes.push(len as u8);
+    } else {
+        let octets: Vec<u8> = (0..8)
+            .rev()
+            .map(|shift| (len >> (shift * 8)) as u8)
+            .skip_while(|&octet| octet == 0)
+            .collect();
+        bytes.push(0x80 | octets.len() as u8);
+        bytes.extend_from_slice(&octets);
+    }
+    bytes.extend_from_slice(content);
+    bytes
+}
+
+pub fn sequence(items: &[Vec<u8>]) -> Vec<u8> {
+    encode(TAG_SEQUENCE, &items.concat())
+}
+
+/// Encodes SET OF `items` sorted in the DER order.
+pub fn set_of(items: &[Vec<u8>]) -> Vec<u8> {
+    let mut items = items.to_vec();
+    items.sort();
+    encode(TAG_SET, &items.concat())
+}
+
+/// Encodes context-specific constructed value `[number]`.
+pub fn context(number: u8, content: &[u8]) -> Vec<u8> {
+    encode(context_tag(number), content)
+}
+
+/// Encodes unsigned big-endian `value` as INTEGER.
+pub fn unsigned_integer(value: &[u8]) -> Vec<u8> {
+    let mut content: Vec<u8> = value.iter().cloned().skip_while(|&octet| octet == 0).collect();
+    if content.first().map_or(true, |&octet| octet & 0x80 != 0) {
+        content.insert(0, 0);
+    }
+    encode(TAG_INTEGER, &content)
+}
+
+pub fn integer(value: u64) -> Vec<u8> {
+    let bytes: Vec<u8> = (0..8).rev().map(|shift| (value >> (shift * 8)) as u8).collect();
+    unsigned_integer(&bytes)
+}
+
+pub fn oid(arcs: &[u64]) -> Vec<u8> {
+    let mut content = Vec::new();
+    let first = arcs[0] * 40 + arcs[1];
+    for &arc in [first].iter().chain(arcs[2..].iter()) {
+        let mut octets = vec![(arc & 0x7f) as u8];
+        let mut arc = arc >> 7;
+        while arc > 0 {
+            octets.push(0x80 | (arc & 0x7f) as u8);
+            arc >>= 7;
+        }
+        octets.reverse();
+        content.extend(octets);
+    }
+    encode(TAG_OID, &content)
+}
+
+pub fn octet_string(value: &[u8]) -> Vec<u8> {
+    encode(TAG_OCTET_STRING, value)
+}
+
+pub fn utf8_string(value: &str) -> Vec<u8> {
+    encode(TAG_UTF8_STRING, value.as_bytes())
+}
+
+/// Encodes BIT STRING with the single `bit` set, as used by named bit lists.
+pub fn bit_string_flag(bit: usize) -> Vec<u8> {
+    let mut content = vec![0u8; bit / 8 + 2];
+    content[0] = (7 - bit % 8) as u8;
+    content[bit / 8 + 1] = 0x80 >> (bit % 8);
+    encode(TAG_BIT_STRING, &content)
+}
+
+/// Encodes GeneralizedTime with the fraction of seconds, trailing zeros are omitted.
+pub fn generalized_time(datetime: &DateTime<Utc>) -> Vec<u8> {
+    let mut value = datetime.format("%Y%m%d%H%M%S").to_string();
+    let nanos = datetime.timestamp_subsec_nanos();
+    if nanos > 0 {
+        value.push('.');
+        value.push_str(format!("{:09}", nanos).trim_right_matches('0'));
+    }
+    value.push('Z');
+    encode(TAG_GENERALIZED_TIME, value.as_bytes())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_read_short_and_long_lengths() {
+        for &len in &[0usize, 1, 0x7f, 0x80, 0xff, 0x100, 0x1_0000] {
+            let content = vec![0x5a; len];
+            let encoded = encode(TAG_OCTET_STRING, &content);
+            let mut reader = Reader::new(&encoded);
+            let (tag, raw, value) = reader.read_any().unwrap();
+            assert_eq!(tag, TAG_OCTET_STRING);
+            assert_eq!(raw, &encoded[..]);
+            assert_eq!(value, &content[..]);
+            assert!(reader.is_empty());
+        }
+        assert_eq!(encode(TAG_OCTET_STRING, &[0; 0x80])[..3], [0x04, 0x81, 0x80]);
+        assert_eq!(encode(TAG_OCTET_STRING, &[0; 0x100])[..4], [0x04, 0x82, 0x01, 0x00]);
+    }
+
+    #[test]
+    fn test_read_truncated() {
+        let truncated: &[&[u8]] = &[
+            &[],
+            &[0x04],
+            &[0x04, 0x03, 0x01, 0x02],
+            &[0x04, 0x82, 0x01],
+            &[0x04, 0x81, 0x80, 0x00],
+        ];
+        for data in truncated {
+            match Reader::new(data).read_any() {
+                Err(DerError::UnexpectedEnd) => {}
+                result => panic!("{:?}: unexpected result {:?}", data, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_unsupported_lengths() {
+        let unsupported: &[&[u8]] = &[
+            // indefinite length
+            &[0x30, 0x80, 0x04, 0x00, 0x00, 0x00],
+            // more than 4 length octets
+            &[0x04, 0x85, 0x00, 0x00, 0x00, 0x00, 0x01, 0x00],
+            // long form of the short length
+            &[0x04, 0x81, 0x01, 0x00],
+            // leading zero length octet
+            &[0x04, 0x82, 0x00, 0x80],
+        ];
+        for data in unsupported {
+            match Reader::new(data).read_any() {
+                Err(DerError::UnsupportedLength) => {}
+                result => panic!("{:?}: unexpected result {:?}", data, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_read_huge_length() {
+        match Reader::new(&[0x04, 0x84, 0xff, 0xff, 0xff, 0xff, 0x00]).read_any() {
+            Err(DerError::UnexpectedEnd) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_read_unexpected_tag() {
+        let mut reader = Reader::new(&[0x02, 0x01, 0x01]);
+        match reader.read(TAG_SEQUENCE) {
+            Err(DerError::UnexpectedTag(TAG_INTEGER, TAG_SEQUENCE)) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+        assert_eq!(reader.read_optional(TAG_BOOLEAN).unwrap(), None);
+        assert_eq!(reader.read_optional(TAG_INTEGER).unwrap(), Some(&[0x01][..]));
+        assert!(reader.is_empty());
+    }
+
+    #[test]
+    fn test_parse_oid() {
+        let sha256 = [0x60, 0x86, 0x48, 0x01, 0x65, 0x03, 0x04, 0x02, 0x01];
+        assert_eq!(parse_oid(&sha256).unwrap(), vec![2, 16, 840, 1, 101, 3, 4, 2, 1]);
+        assert_eq!(parse_oid(&[0x2b, 0x65, 0x70]).unwrap(), vec![1, 3, 101, 112]);
+        assert_eq!(parse_oid(&[0x27]).unwrap(), vec![0, 39]);
+        assert_eq!(parse_oid(&[0x50]).unwrap(), vec![2, 0]);
+        // the first arc is 2 and the second one is at least 40
+        assert_eq!(parse_oid(&[0x88, 0x37, 0x03]).unwrap(), vec![2, 999, 3]);
+    }
+
+    #[test]
+    fn test_parse_invalid_oid() {
+        let invalid: &[&[u8]] = &[
+            &[],
+            // trailing continuation octet
+            &[0x2b, 0x86],
+            &[0x86],
+            // leading zero octet of the subidentifier
+            &[0x2b, 0x80, 0x01],
+            // subidentifier doesn't fit into u64
+            &[0x2b, 0x82, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0x7f],
+        ];
+        for content in invalid {
+            match parse_oid(content) {
+                Err(DerError::InvalidValue) => {}
+                result => panic!("{:?}: unexpected result {:?}", content, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_oid_round_trip() {
+        let oids: &[&[u64]] = &[
+            &[2, 16, 840, 1, 101, 3, 4, 2, 3],
+            &[1, 3, 6, 1, 4, 1, 32473, 42, 2],
+            &[2, 999, 3],
+            &[0, 0],
+        ];
+        for &arcs in oids {
+            let encoded = oid(arcs);
+            let content = Reader::new(&encoded).read(TAG_OID).unwrap();
+            assert_eq!(parse_oid(content).unwrap(), arcs.to_vec());
+        }
+    }
+
+    #[test]
+    fn test_integer_round_trip() {
+        assert_eq!(integer(0), vec![0x02, 0x01, 0x00]);
+        assert_eq!(integer(0x7f), vec![0x02, 0x01, 0x7f]);
+        assert_eq!(integer(0x80), vec![0x02, 0x02, 0x00, 0x80]);
+        assert_eq!(integer(0x100), vec![0x02, 0x02, 0x01, 0x00]);
+        assert_eq!(unsigned_integer(&[0x00, 0x00, 0xff]), vec![0x02, 0x02, 0x00, 0xff]);
+
+        for &value in &[0, 1, 0x7f, 0x80, 0xffff, 1 << 40, u64::max_value()] {
+            let encoded = integer(value);
+            let content = Reader::new(&encoded).read(TAG_INTEGER).unwrap();
+            assert_eq!(parse_u64(content).unwrap(), value);
+        }
+    }
+
+    #[test]
+    fn test_parse_invalid_u64() {
+        let invalid: &[&[u8]] = &[
+            &[],
+            // negative
+            &[0x80],
+            // more than 8 octets
+            &[0x01, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00],
+        ];
+        for content in invalid {
+            match parse_u64(content) {
+                Err(DerError::InvalidValue) => {}
+                result => panic!("{:?}: unexpected result {:?}", content, result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_bit_string_flag() {
+        assert_eq!(bit_string_flag(0), vec![0x03, 0x02, 0x07, 0x80]);
+        assert_eq!(bit_string_flag(2), vec![0x03, 0x02, 0x05, 0x20]);
+        assert_eq!(bit_string_flag(7), vec![0x03, 0x02, 0x00, 0x01]);
+        assert_eq!(bit_string_flag(25), vec![0x03, 0x05, 0x06, 0x00, 0x00, 0x00, 0x40]);
+
+        for bit in 0..32 {
+            let encoded = bit_string_flag(bit);
+            let content = Reader::new(&encoded).read(TAG_BIT_STRING).unwrap();
+            let unused = content[0] as usize;
+            let bits = &content[1..];
+            assert_eq!(bits.len() * 8 - unused, bit + 1);
+            assert_eq!(bits[bit / 8] & (0x80 >> (bit % 8)), 0x80 >> (bit % 8));
+            assert_eq!(bits.iter().map(|octet| octet.count_ones()).sum::<u32>(), 1);
+        }
+    }
+
+    #[test]
+    fn test_set_of_is_sorted() {
+        let encoded = set_of(&[integer(2), integer(1)]);
+        assert_eq!(encoded, vec![0x31, 0x06, 0x02, 0x01, 0x01, 0x02, 0x01, 0x02]);
+    }
+}
@@ -0,0 +1,505 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use exonum::crypto::{self, Hash, PublicKey, SecretKey};
+use exonum::encoding::serialize::ToHex;
+use sha2::{Digest, Sha512};
+
+use timestamping::schema::Timestamp;
+use timestamping::digest::HashAlgorithm;
+use super::der::{self, DerError, Reader};
+
+pub const OID_SHA256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 1];
+pub const OID_SHA512: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 3];
+pub const OID_SHA3_256: &[u64] = &[2, 16, 840, 1, 101, 3, 4, 2, 8];
+pub const OID_ED25519: &[u64] = &[1, 3, 101, 112];
+pub const OID_SIGNED_DATA: &[u64] = &[1, 2, 840, 113549, 1, 7, 2];
+pub const OID_TST_INFO: &[u64] = &[1, 2, 840, 113549, 1, 9, 16, 1, 4];
+pub const OID_CONTENT_TYPE: &[u64] = &[1, 2, 840, 113549, 1, 9, 3];
+pub const OID_MESSAGE_DIGEST: &[u64] = &[1, 2, 840, 113549, 1, 9, 4];
+pub const OID_COMMON_NAME: &[u64] = &[2, 5, 4, 3];
+
+/// TSA policy of the tokens issued by the node
+///
+/// The OID is under the documentation arc of RFC 5612,
+/// deployments are expected to replace it with their own.
+pub const OID_TSA_POLICY: &[u64] = &[1, 3, 6, 1, 4, 1, 32473, 42, 1];
+
+/// Non-critical `TSTInfo` extension with the block reference of the timestamp:
+///
+/// BlockReference ::= SEQUENCE {
+///     height   INTEGER,
+///     blockHash OCTET STRING,
+///     txHash   OCTET STRING }
+pub const OID_BLOCK_REFERENCE: &[u64] = &[1, 3, 6, 1, 4, 1, 32473, 42, 2];
+
+/// `PKIStatus` values
+const STATUS_GRANTED: u64 = 0;
+const STATUS_REJECTION: u64 = 2;
+
+/// `PKIFailureInfo` bits
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum PkiFailure {
+    BadAlg = 0,
+    BadRequest = 2,
+    BadDataFormat = 5,
+    TimeNotAvailable = 14,
+    UnacceptedPolicy = 15,
+    UnacceptedExtension = 16,
+    SystemFailure = 25,
+}
+
+/// Parsed `TimeStampReq` of RFC 3161
+#[derive(Debug, Clone)]
+pub struct TimeStampReq {
+    /// `MessageImprint` DER, echoed in the `TSTInfo`
+    pub message_imprint: Vec<u8>,
+    pub hash_algorithm: Vec<u64>,
+    pub hashed_message: Vec<u8>,
+    pub req_policy: Option<Vec<u64>>,
+    /// `nonce` INTEGER content, echoed in the `TSTInfo`
+    pub nonce: Option<Vec<u8>>,
+    pub cert_req: bool,
+    /// Whether the request has `extensions`, none of them are supported
+    pub has_extensions: bool,
+}
+
+impl TimeStampReq {
+    /// Algorithm of the `messageImprint`, `None` in the case of it's not supported
+    pub fn algorithm(&self) -> Option<HashAlgorithm> {
+        match self.hash_algorithm.as_slice() {
+            oid if oid == OID_SHA256 => Some(HashAlgorithm::Sha256),
+            oid if oid == OID_SHA512 => Some(HashAlgorithm::Sha512),
+            oid if oid == OID_SHA3_256 => Some(HashAlgorithm::Sha3_256),
+            _ => None,
+        }
+    }
+
+    /// Checks the request can be granted, provides the algorithm of the `messageImprint`.
+    pub fn check(&self) -> Result<HashAlgorithm, (PkiFailure, String)> {
+        let algorithm = match self.algorithm() {
+            Some(algorithm) => algorithm,
+            None => return Err((PkiFailure::BadAlg, "Unsupported hash algorithm".to_owned())),
+        };
+        if self.hashed_message.len() != algorithm.digest_len() {
+            return Err((PkiFailure::BadDataFormat, "Invalid hash length".to_owned()));
+        }
+        if self.req_policy.as_ref().map_or(false, |policy| policy != OID_TSA_POLICY) {
+            return Err((PkiFailure::UnacceptedPolicy, "Unknown TSA policy".to_owned()));
+        }
+        // RFC 3161 requires to reject requests with unrecognized extensions
+        if self.has_extensions {
+            let text = "Extensions are not supported".to_owned();
+            return Err((PkiFailure::UnacceptedExtension, text));
+        }
+        Ok(algorithm)
+    }
+
+    /// Parses DER encoded `TimeStampReq`:
+    ///
+    /// TimeStampReq ::= SEQUENCE {
+    ///     version          INTEGER { v1(1) },
+    ///     messageImprint   MessageImprint,
+    ///     reqPolicy        TSAPolicyId              OPTIONAL,
+    ///     nonce            INTEGER                  OPTIONAL,
+    ///     certReq          BOOLEAN                  DEFAULT FALSE,
+    ///     extensions       [0] IMPLICIT Extensions  OPTIONAL }
+    pub fn from_der(data: &[u8]) -> Result<TimeStampReq, DerError> {
+        let mut outer = Reader::new(data);
+        let mut reader = Reader::new(outer.read(der::TAG_SEQUENCE)?);
+        if !outer.is_empty() {
+            return Err(DerError::InvalidValue);
+        }
+
+        if der::parse_u64(reader.read(der::TAG_INTEGER)?)? != 1 {
+            return Err(DerError::InvalidValue);
+        }
+
+        let message_imprint = reader.read_raw(der::TAG_SEQUENCE)?;
+        let mut imprint = Reader::new(Reader::new(message_imprint).read(der::TAG_SEQUENCE)?);
+        let mut algorithm = Reader::new(imprint.read(der::TAG_SEQUENCE)?);
+        let hash_algorithm = der::parse_oid(algorithm.read(der::TAG_OID)?)?;
+        let hashed_message = imprint.read(der::TAG_OCTET_STRING)?;
+
+        let req_policy = match reader.read_optional(der::TAG_OID)? {
+            Some(policy) => Some(der::parse_oid(policy)?),
+            None => None,
+        };
+        let nonce = reader.read_optional(der::TAG_INTEGER)?.map(<[u8]>::to_vec);
+        let cert_req = match reader.read_optional(der::TAG_BOOLEAN)? {
+            Some(value) => value.first().map_or(false, |&value| value != 0),
+            None => false,
+        };
+        let has_extensions = reader.read_optional(der::context_tag(0))?.is_some();
+        if !reader.is_empty() {
+            return Err(DerError::InvalidValue);
+        }
+
+        Ok(TimeStampReq {
+            message_imprint: message_imprint.to_vec(),
+            hash_algorithm,
+            hashed_message: hashed_message.to_vec(),
+            req_policy,
+            nonce,
+            cert_req,
+            has_extensions,
+        })
+    }
+}
+
+/// Builds `TimeStampResp` rejecting the request with the given `failure`.
+pub fn rejection(failure: PkiFailure, text: &str) -> Vec<u8> {
+    let status = der::sequence(&[
+        der::integer(STATUS_REJECTION),
+        der::sequence(&[der::utf8_string(text)]),
+        der::bit_string_flag(failure as usize),
+    ]);
+    der::sequence(&[status])
+}
+
+/// Builds `TimeStampResp` granting the request with the token for the committed `timestamp`.
+///
+/// The token is CMS `SignedData` over `TSTInfo` signed by the node service key with Ed25519
+/// (RFC 8419). No certificates are included, so `certReq` is ignored, see `signer_name`
+/// for the signer identification.
+///
+/// `serial` is the unique `serialNumber` of the token, unsigned big-endian.
+pub fn granted(
+    request: &TimeStampReq,
+    timestamp: &Timestamp,
+    block_hash: Option<&Hash>,
+    serial: &[u8],
+    keys: &(PublicKey, SecretKey),
+) -> Vec<u8> {
+    let tst_info = tst_info(request, timestamp, block_hash, serial);
+    let token = der::sequence(&[
+        der::oid(OID_SIGNED_DATA),
+        der::context(0, &signed_data(&tst_info, keys)),
+    ]);
+    let status = der::sequence(&[der::integer(STATUS_GRANTED)]);
+    der::sequence(&[status, token])
+}
+
+/// TSTInfo ::= SEQUENCE {
+///     version         INTEGER { v1(1) },
+///     policy          TSAPolicyId,
+///     messageImprint  MessageImprint,
+///     serialNumber    INTEGER,
+///     genTime         GeneralizedTime,
+///     nonce           INTEGER                 OPTIONAL,
+///     extensions      [1] IMPLICIT Extensions OPTIONAL }
+///
+/// The block reference extension is omitted for the migrated records, whose block is unknown.
+fn tst_info(
+    request: &TimeStampReq,
+    timestamp: &Timestamp,
+    block_hash: Option<&Hash>,
+    serial: &[u8],
+) -> Vec<u8> {
+    let mut fields = vec![
+        der::integer(1),
+        der::oid(OID_TSA_POLICY),
+        request.message_imprint.clone(),
+        der::unsigned_integer(serial),
+        der::generalized_time(&timestamp.datetime()),
+    ];
+    if let Some(ref nonce) = request.nonce {
+        fields.push(der::encode(der::TAG_INTEGER, nonce));
+    }
+    if let Some(block_hash) = block_hash {
+        let block_reference = der::sequence(&[
+            der::integer(timestamp.height()),
+            der::octet_string(block_hash.as_ref()),
+            der::octet_string(timestamp.tx_hash().as_ref()),
+        ]);
+        let extension = der::sequence(&[
+            der::oid(OID_BLOCK_REFERENCE),
+            der::octet_string(&block_reference),
+        ]);
+        fields.push(der::context(1, &extension));
+    }
+    der::sequence(&fields)
+}
+
+/// SignedData ::= SEQUENCE {
+///     version           CMSVersion,
+///     digestAlgorithms  DigestAlgorithmIdentifiers,
+///     encapContentInfo  EncapsulatedContentInfo,
+///     signerInfos       SignerInfos }
+fn signed_data(tst_info: &[u8], keys: &(PublicKey, SecretKey)) -> Vec<u8> {
+    let digest_algorithm = der::sequence(&[der::oid(OID_SHA512)]);
+    let encap_content_info = der::sequence(&[
+        der::oid(OID_TST_INFO),
+        der::context(0, &der::octet_string(tst_info)),
+    ]);
+
+    let mut hasher = Sha512::default();
+    hasher.input(tst_info);
+    let message_digest = hasher.result().to_vec();
+
+    let signed_attrs = [
+        der::sequence(&[
+            der::oid(OID_CONTENT_TYPE),
+            der::set_of(&[der::oid(OID_TST_INFO)]),
+        ]),
+        der::sequence(&[
+            der::oid(OID_MESSAGE_DIGEST),
+            der::set_of(&[der::octet_string(&message_digest)]),
+        ]),
+    ];
+    // Signature is calculated over `SET OF` encoding of the attributes,
+    // while `[0] IMPLICIT` tag is used in the `SignerInfo`.
+    let signed_attrs_set = der::set_of(&signed_attrs);
+    let signature = crypto::sign(&signed_attrs_set, &keys.1);
+    let mut signed_attrs_implicit = signed_attrs_set.clone();
+    signed_attrs_implicit[0] = der::context_tag(0);
+
+    let issuer_and_serial = der::sequence(&[
+        signer_name(&keys.0),
+        der::unsigned_integer(&keys.0.as_ref()[..16]),
+    ]);
+    let signer_info = der::sequence(&[
+        der::integer(1),
+        issuer_and_serial,
+        digest_algorithm.clone(),
+        signed_attrs_implicit,
+        der::sequence(&[der::oid(OID_ED25519)]),
+        der::octet_string(signature.as_ref()),
+    ]);
+
+    der::sequence(&[
+        der::integer(3),
+        der::set_of(&[digest_algorithm]),
+        encap_content_info,
+        der::set_of(&[signer_info]),
+    ])
+}
+
+/// Name of the signer: `CN=Exonum TSA <service key hex>`
+///
+/// The signer is identified by `issuerAndSerialNumber` with this name and the first 16 bytes
+/// of the service key as the serial number. There is no such certificate, but the tooling
+/// (e.g. OpenSSL) doesn't parse tokens with `subjectKeyIdentifier` of the signer.
+pub fn signer_name(public_key: &PublicKey) -> Vec<u8> {
+    let common_name = format!("Exonum TSA {}", public_key.to_hex());
+    der::sequence(&[der::set_of(&[der::sequence(&[
+        der::oid(OID_COMMON_NAME),
+        der::utf8_string(&common_name),
+    ])])])
+}
+
+#[cfg(test)]
+mod tests {
+    use chrono::{TimeZone, Utc};
+    use exonum::crypto::{gen_keypair_from_seed, hash, Seed};
+    use exonum::encoding::serialize::FromHex;
+    use exonum::helpers::Height;
+
+    use super::*;
+
+    /// `openssl ts -query -data hello.txt -sha256 -no_nonce`
+    const QUERY_SHA256: &str = concat!(
+        "30360201013031300d060960864801650304020105000420b94d27b9934d3e08a52e52d7da7dabfac484efe3",
+        "7a5380ee9088f7ace2efcde9",
+    );
+
+    /// `openssl ts -query -data hello.txt -sha256 -tspolicy 1.3.6.1.4.1.32473.42.1 -cert`
+    const QUERY_POLICY_NONCE: &str = concat!(
+        "304f0201013031300d060960864801650304020105000420b94d27b9934d3e08a52e52d7da7dabfac484efe3",
+        "7a5380ee9088f7ace2efcde9060a2b0601040181fd592a010208621cf7a204fdf2c30101ff",
+    );
+
+    /// `openssl ts -query -data hello.txt -sha512 -no_nonce`
+    const QUERY_SHA512: &str = concat!(
+        "30560201013051300d060960864801650304020305000440309ecc489c12d6eb4cc40f50c902f2b4d0ed77ee",
+        "511a7c7a9bcd3ca86d4cd86f989dd35bc5ff499670da34255b45b0cfd830e81f605dcf7dc5542e93ae9cd76f",
+    );
+
+    /// `TimeStampResp` granting `QUERY_POLICY_NONCE` by `golden_keys`
+    const GOLDEN_REPLY: &str = concat!(
+        "3082025830030201003082024f06092a864886f70d010702a08202403082023c020103310d300b0609608648",
+        "0165030402033081ea060b2a864886f70d0109100104a081da0481d73081d4020101060a2b0601040181fd59",
+        "2a013031300d060960864801650304020105000420b94d27b9934d3e08a52e52d7da7dabfac484efe37a5380",
+        "ee9088f7ace2efcde902100102030405060708090a0b0c0d0e0f10181932303138303430313132353633302e",
+        "3039373130313231395a0208621cf7a204fdf2c3a1593057060a2b0601040181fd592a020449304702010c04",
+        "20496aca80e4d8f29fb8e8cd816c3afb48d3f103970b3a2ee1600c08ca67326dee0420ce922519a3c3ecaf9b",
+        "0986c2449c7680895c15f4b0e9818e994e14a4d28b6aaf3182013930820135020101306b3056315430520603",
+        "5504030c4b45786f6e756d205453412065613461366336336532396335323061626566353530376231333265",
+        "633566393935343737366165626562653762393234323165656136393134343664323263021100ea4a6c63e2",
+        "9c520abef5507b132ec5f9300b0609608648016503040203a06d301a06092a864886f70d010903310d060b2a",
+        "864886f70d0109100104304f06092a864886f70d0109043142044097ad2356f3075a31ed9a2684f232163a0b",
+        "ef86fffb8f585973d009ab51c0dfae0cfecb0180752e99caf6e8325c07fffa5a5748bef3e57765e9180a7a07",
+        "622a00300506032b65700440977650f7533852d231b88edf8d458324236cde4b7f9e80717bd6a30b1210a4b8",
+        "0ffe53a30e32d2c073c92118cbaf6482ac72e70d6aa5fe11e58c28430a6fc704",
+    );
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        Vec::<u8>::from_hex(hex).unwrap()
+    }
+
+    fn golden_keys() -> (PublicKey, SecretKey) {
+        gen_keypair_from_seed(&Seed::new([7; 32]))
+    }
+
+    #[test]
+    fn test_parse_query() {
+        let query = bytes(QUERY_SHA256);
+        let request = TimeStampReq::from_der(&query).unwrap();
+        assert_eq!(request.algorithm(), Some(HashAlgorithm::Sha256));
+        assert_eq!(request.hashed_message, hash(b"hello world").as_ref().to_vec());
+        assert_eq!(request.message_imprint, query[5..].to_vec());
+        assert_eq!(request.req_policy, None);
+        assert_eq!(request.nonce, None);
+        assert!(!request.cert_req);
+    }
+
+    #[test]
+    fn test_parse_query_with_policy_and_nonce() {
+        let request = TimeStampReq::from_der(&bytes(QUERY_POLICY_NONCE)).unwrap();
+        assert_eq!(request.algorithm(), Some(HashAlgorithm::Sha256));
+        assert_eq!(request.req_policy, Some(OID_TSA_POLICY.to_vec()));
+        assert_eq!(request.nonce, Some(bytes("621cf7a204fdf2c3")));
+        assert!(request.cert_req);
+    }
+
+    #[test]
+    fn test_parse_sha512_query() {
+        let request = TimeStampReq::from_der(&bytes(QUERY_SHA512)).unwrap();
+        assert_eq!(request.algorithm(), Some(HashAlgorithm::Sha512));
+        assert_eq!(request.hashed_message, HashAlgorithm::Sha512.digest(b"hello world"));
+    }
+
+    #[test]
+    fn test_parse_malformed_query() {
+        let query = bytes(QUERY_POLICY_NONCE);
+        for len in 0..query.len() {
+            assert!(TimeStampReq::from_der(&query[..len]).is_err(), "truncated to {}", len);
+        }
+
+        let mut trailing = query.clone();
+        trailing.push(0);
+        match TimeStampReq::from_der(&trailing) {
+            Err(DerError::InvalidValue) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let mut version = query.clone();
+        version[4] = 2;
+        match TimeStampReq::from_der(&version) {
+            Err(DerError::InvalidValue) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_parse_query_with_trailing_data() {
+        let mut query = bytes(QUERY_SHA256);
+        query.extend_from_slice(&der::octet_string(&[0]));
+        query[1] += 3;
+        match TimeStampReq::from_der(&query) {
+            Err(DerError::InvalidValue) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_query_with_extensions() {
+        let extension = der::sequence(&[der::oid(OID_BLOCK_REFERENCE), der::octet_string(&[])]);
+        let mut query = bytes(QUERY_SHA256);
+        let extensions = der::context(0, &extension);
+        query.extend_from_slice(&extensions);
+        query[1] += extensions.len() as u8;
+
+        let request = TimeStampReq::from_der(&query).unwrap();
+        assert!(request.has_extensions);
+        match request.check() {
+            Err((PkiFailure::UnacceptedExtension, _)) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+
+        let request = TimeStampReq::from_der(&bytes(QUERY_SHA256)).unwrap();
+        assert!(!request.has_extensions);
+        assert_eq!(request.check().unwrap(), HashAlgorithm::Sha256);
+    }
+
+    #[test]
+    fn test_rejection() {
+        let reply = rejection(PkiFailure::BadAlg, "Unsupported hash algorithm");
+        let mut reader = Reader::new(Reader::new(&reply).read(der::TAG_SEQUENCE).unwrap());
+        let mut status = Reader::new(reader.read(der::TAG_SEQUENCE).unwrap());
+        assert!(reader.is_empty());
+        assert_eq!(der::parse_u64(status.read(der::TAG_INTEGER).unwrap()).unwrap(), 2);
+        status.read(der::TAG_SEQUENCE).unwrap();
+        assert_eq!(status.read(der::TAG_BIT_STRING).unwrap(), &[0x07, 0x80][..]);
+    }
+
+    #[test]
+    fn test_golden_granted() {
+        let keys = golden_keys();
+        assert_eq!(
+            keys.0,
+            PublicKey::from_hex("ea4a6c63e29c520abef5507b132ec5f9954776aebebe7b92421eea691446d22c")
+                .unwrap()
+        );
+
+        let request = TimeStampReq::from_der(&bytes(QUERY_POLICY_NONCE)).unwrap();
+        let timestamp = Timestamp::from_parts(
+            &Utc.timestamp(1_522_587_390, 97_101_219),
+            HashAlgorithm::Sha256,
+            &request.hashed_message,
+            &PublicKey::zero(),
+            Height(12),
+            "",
+            &hash(b"transaction")
+        );
+        let serial: Vec<u8> = (1..17).collect();
+        let reply = granted(&request, &timestamp, Some(&hash(b"block")), &serial, &keys);
+        assert_eq!(reply, bytes(GOLDEN_REPLY));
+    }
+
+    #[test]
+    fn test_granted_signature() {
+        let keys = golden_keys();
+        let reply = bytes(GOLDEN_REPLY);
+
+        // TimeStampResp -> token ContentInfo -> SignedData -> SignerInfo
+        let mut response = Reader::new(Reader::new(&reply).read(der::TAG_SEQUENCE).unwrap());
+        response.read(der::TAG_SEQUENCE).unwrap();
+        let mut token = Reader::new(response.read(der::TAG_SEQUENCE).unwrap());
+        token.read(der::TAG_OID).unwrap();
+        let signed_data = Reader::new(token.read(der::context_tag(0)).unwrap())
+            .read(der::TAG_SEQUENCE)
+            .unwrap();
+        let mut signed_data = Reader::new(signed_data);
+        signed_data.read(der::TAG_INTEGER).unwrap();
+        signed_data.read(der::TAG_SET).unwrap();
+        signed_data.read(der::TAG_SEQUENCE).unwrap();
+        let mut signer_infos = Reader::new(signed_data.read(der::TAG_SET).unwrap());
+        let mut signer_info = Reader::new(signer_infos.read(der::TAG_SEQUENCE).unwrap());
+        signer_info.read(der::TAG_INTEGER).unwrap();
+        let mut issuer_and_serial = Reader::new(signer_info.read(der::TAG_SEQUENCE).unwrap());
+        let issuer = issuer_and_serial.read_raw(der::TAG_SEQUENCE).unwrap();
+        assert_eq!(issuer, &signer_name(&keys.0)[..]);
+        signer_info.read(der::TAG_SEQUENCE).unwrap();
+        let mut signed_attrs = signer_info.read_raw(der::context_tag(0)).unwrap().to_vec();
+        signer_info.read(der::TAG_SEQUENCE).unwrap();
+        let signature = signer_info.read(der::TAG_OCTET_STRING).unwrap();
+
+        // The signature is made over `SET OF` encoding of the attributes.
+        signed_attrs[0] = der::TAG_SET;
+        let signature = crypto::Signature::from_slice(signature).unwrap();
+        assert!(crypto::verify(&signature, &signed_attrs, &keys.0));
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+pub mod der;
+pub mod messages;
+
+use std::io::Read;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use exonum::blockchain::{ApiContext, Blockchain, Schema, Transaction};
+use exonum::node::{TransactionSend, ApiSender};
+use exonum::crypto::{hash, Hash, PublicKey, SecretKey};
+use exonum::api::Api;
+use iron::prelude::*;
+use iron::status::Status;
+use iron::headers::ContentType;
+use iron::mime::Mime;
+use iron::modifiers::Header;
+use router::Router;
+
+use timestamping::schema::{Timestamp, TimestampingSchema};
+use timestamping::transactions::{Tx, TxDigest};
+use timestamping::digest::HashAlgorithm;
+use timestamping::status::{TransactionStatus, Waiters};
+use self::messages::{PkiFailure, TimeStampReq};
+
+/// Maximum size of the DER encoded `TimeStampReq`
+pub const MAX_QUERY_LEN: u64 = 16 * 1024;
+/// Maximum time in seconds the query waits for the timestamp commit
+pub const QUERY_TIMEOUT_SECS: u64 = 30;
+
+/// RFC 3161 Time-Stamp Protocol over HTTP for `TimestampingService`
+///
+/// Requests and replies follow the RFC 3161 syntax, so standard tooling creates the queries
+/// and parses the tokens. The tokens are not verifiable by it though: they are signed with
+/// Ed25519 and carry neither the TSA certificate nor the `signingCertificateV2` attribute,
+/// so clients check the signature against the node service key and prove the timestamp
+/// by `/v0/timestamp/:data_hash/proof`.
+#[derive(Clone)]
+pub struct Rfc3161Api {
+    channel: ApiSender,
+    blockchain: Blockchain,
+    service_keys: (PublicKey, SecretKey),
+    waiters: Waiters,
+    /// Number of tokens issued since the node start
+    issued: Arc<AtomicUsize>
+}
+
+impl Rfc3161Api {
+    /// Constructs a `Rfc3161Api` for the given `context`,
+    /// `waiters` limit the queries waiting for commit.
+    pub fn new(context: &ApiContext, waiters: Waiters) -> Rfc3161Api {
+        let channel = context.node_channel().clone();
+        let blockchain = context.blockchain().clone();
+        let service_keys = (*context.public_key(), context.secret_key().clone());
+
+        Rfc3161Api {
+            channel,
+            blockchain,
+            service_keys,
+            waiters,
+            issued: Arc::new(AtomicUsize::new(0))
+        }
+    }
+
+    /// Creates timestamp for the `messageImprint` of DER encoded `TimeStampReq`
+    /// and responds with DER encoded `TimeStampResp`
+    ///
+    /// SHA-256, SHA-512 and SHA3-256 imprints are accepted, the imprint is timestamped
+    /// as the digest of the corresponding `HashAlgorithm`.
+    /// The request blocks up to `QUERY_TIMEOUT_SECS` until the timestamp is committed,
+    /// existing timestamp of the same `data_hash` is returned without new transaction.
+    /// At most `MAX_WAITERS` requests wait for commit simultaneously (shared with the `wait`
+    /// requests of `TimestampingApi`), the rest are rejected with `systemFailure`.
+    ///
+    /// # Example
+    /// $ openssl ts -query -data contract.pdf -sha256 -no_nonce -out query.tsq
+    /// $ curl -H "Content-Type: application/timestamp-query" --data-binary @query.tsq \
+    ///     http://127.0.0.1:8200/api/services/timestamping/v0/rfc3161 > reply.tsr
+    /// $ openssl ts -reply -in reply.tsr -text
+    ///
+    /// The token is signed by the node service key with Ed25519 and has no certificates,
+    /// so `certReq` is ignored. `TSTInfo` carries the non-critical extension
+    /// `OID_BLOCK_REFERENCE` with the height, block hash and transaction hash of the timestamp.
+    ///
+    /// `openssl ts -verify` is not supported, see `Rfc3161Api` for the token verification.
+    ///
+    fn post_query(&self, req: &mut Request) -> IronResult<Response> {
+        let mut body = Vec::new();
+        let reply = match req.body.by_ref().take(MAX_QUERY_LEN + 1).read_to_end(&mut body) {
+            Err(e) => messages::rejection(PkiFailure::BadRequest, &e.to_string()),
+            Ok(len) if len as u64 > MAX_QUERY_LEN => {
+                messages::rejection(PkiFailure::BadDataFormat, "Request is too large")
+            }
+            Ok(_) => match TimeStampReq::from_der(&body) {
+                Ok(request) => self.timestamp(&request).unwrap_or_else(|(failure, text)| {
+                    messages::rejection(failure, &text)
+                }),
+                Err(e) => messages::rejection(PkiFailure::BadDataFormat, &e.to_string()),
+            },
+        };
+
+        let content_type: Mime = "application/timestamp-reply".parse().unwrap();
+        Ok(Response::with((Status::Ok, Header(ContentType(content_type)), reply)))
+    }
+
+    /// Provides `TimeStampResp` granting the `request`, creates the timestamp if necessary.
+    fn timestamp(&self, request: &TimeStampReq) -> Result<Vec<u8>, (PkiFailure, String)> {
+        let algorithm = request.check()?;
+
+        let data_hash = algorithm.storage_key(&request.hashed_message);
+        if let Some(reply) = self.granted(request, &data_hash) {
+            return Ok(reply);
+        }
+
+        let _waiter = match self.waiters.acquire() {
+            Some(waiter) => waiter,
+            None => {
+                return Err((PkiFailure::SystemFailure, "Too many pending queries".to_owned()))
+            }
+        };
+        let (pub_key, ref secret_key) = self.service_keys;
+        let transaction: Box<Transaction> = match algorithm {
+            HashAlgorithm::Sha256 => Tx::new(&pub_key, &data_hash, secret_key).into(),
+            _ => {
+                let digest = &request.hashed_message;
+                TxDigest::new(&pub_key, algorithm.id(), digest, "", secret_key).into()
+            }
+        };
+        let tx_hash = transaction.hash();
+        self.channel.send(transaction).map_err(|e| {
+            (PkiFailure::SystemFailure, e.to_string())
+        })?;
+
+        let timeout = Duration::from_secs(QUERY_TIMEOUT_SECS);
+        let status = TransactionStatus::wait_for_commit(&self.blockchain, &tx_hash, timeout);
+        // The timestamp may be created by the other transaction while waiting,
+        // so it's looked up regardless of the transaction status.
+        match self.granted(request, &data_hash) {
+            Some(reply) => Ok(reply),
+            None if status.is_final() => {
+                Err((PkiFailure::SystemFailure, "Timestamp transaction failed".to_owned()))
+            }
+            None => {
+                Err((PkiFailure::TimeNotAvailable, "Timestamp is not committed yet".to_owned()))
+            }
+        }
+    }
+
+    /// `TimeStampResp` for the committed timestamp of `data_hash` if any
+    fn granted(&self, request: &TimeStampReq, data_hash: &Hash) -> Option<Vec<u8>> {
+        let snapshot = self.blockchain.snapshot();
+        let timestamp: Timestamp = TimestampingSchema::new(&snapshot).timestamp(data_hash)?;
+        let block_hash = if timestamp.height() > 0 {
+            Schema::new(&snapshot).block_hashes_by_height().get(timestamp.height())
+        } else {
+            None
+        };
+        let serial = self.serial_number(data_hash);
+        Some(messages::granted(
+            request,
+            &timestamp,
+            block_hash.as_ref(),
+            &serial,
+            &self.service_keys,
+        ))
+    }
+
+    /// Unique `serialNumber` of the token issued for `data_hash`
+    ///
+    /// Tokens of the same timestamp are issued for each query, so the serial number
+    /// is derived from the issue time and the number of tokens issued since the node start.
+    fn serial_number(&self, data_hash: &Hash) -> Vec<u8> {
+        let issued_at = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_else(|_| Duration::from_secs(0));
+        let counter = self.issued.fetch_add(1, Ordering::SeqCst);
+        let seed = format!("{}.{:09}:{}", issued_at.as_secs(), issued_at.subsec_nanos(), counter);
+        hash(&[data_hash.as_ref(), seed.as_bytes()].concat()).as_ref()[..16].to_vec()
+    }
+}
+
+impl Api for Rfc3161Api {
+    fn wire(&self, router: &mut Router) {
+        let self_ = self.clone();
+        let post_query = move |req: &mut Request| self_.post_query(req);
+
+        router.post("/v0/rfc3161", post_query, "post_rfc3161_query_rt");
+    }
+}
@@ -13,14 +13,78 @@
 // limitations under the License.
 
 
-use exonum::storage::{Fork, ProofMapIndex, Snapshot, StorageValue};
-use chrono::{DateTime, Utc};
-use exonum::crypto::{Hash, hash};
+use std::collections::HashSet;
+
+use exonum::storage::{Entry, Fork, ListIndex, MapIndex, ProofMapIndex, Snapshot, StorageValue};
+use exonum::crypto::{self, Hash, PublicKey, Signature};
+use exonum::helpers::Height;
+use chrono::{DateTime, TimeZone, Utc};
+
+use super::digest::HashAlgorithm;
+
+
+/// Maximum number of attestations the validator makes after each block commit
+pub const MAX_ATTESTATIONS_PER_BLOCK: usize = 100;
 
 
 /// Base `TimestampingService` storage structure
 encoding_struct! {
     struct Timestamp {
+        /// Ed25519 signature of `TimestampPayload` made by `signer`
+        signature: &Signature,
+        /// timestamp
+        timestamp: i64,
+        /// data hash, the key of the timestamp (see `HashAlgorithm::storage_key`)
+        data_hash: &Hash,
+        /// height of the block timestamp was created in (0 for migrated records)
+        height: u64,
+        /// service key of the validator attested the timestamp
+        signer: &PublicKey,
+        /// key of the timestamp author (zero for migrated records)
+        author: &PublicKey,
+        /// document metainfo
+        metainfo: &str,
+        /// hash of the transaction timestamp was created by (zero for migrated records)
+        tx_hash: &Hash,
+        /// nanoseconds part of the timestamp
+        nanos: u32,
+        /// `HashAlgorithm` id of the document `digest`
+        algorithm: u8,
+        /// document digest
+        digest: &[u8],
+    }
+}
+
+/// Data attested by the validator signature
+encoding_struct! {
+    struct TimestampPayload {
+        /// data hash
+        data_hash: &Hash,
+        /// timestamp
+        timestamp: i64,
+        /// height of the block timestamp was created in
+        height: u64,
+        /// key of the timestamp author
+        author: &PublicKey,
+        /// document metainfo
+        metainfo: &str,
+        /// hash of the transaction timestamp was created by
+        tx_hash: &Hash,
+        /// nanoseconds part of the timestamp
+        nanos: u32,
+        /// `HashAlgorithm` id of the document `digest`
+        algorithm: u8,
+        /// document digest
+        digest: &[u8],
+    }
+}
+
+/// `TimestampingService` storage structure of schema version 0
+///
+/// Version 0 stored `hash(data_hash + timestamp)` as a signature of `Timestamp`,
+/// version 1 stores Ed25519 attestation made by a validator service key.
+encoding_struct! {
+    struct LegacyTimestamp {
         /// (data_hash + timestamp) hashed
         signature: &Hash,
         /// timestamp
@@ -33,18 +97,98 @@ encoding_struct! {
 
 /// imlementation for `Timestamp` creation
 impl Timestamp {
-    pub fn from_parts(datetime: &DateTime<Utc>, data_hash: &Hash) -> Timestamp {
+    /// Creates not yet attested `Timestamp` of the `digest` made with `algorithm`.
+    pub fn from_parts(
+        datetime: &DateTime<Utc>,
+        algorithm: HashAlgorithm,
+        digest: &[u8],
+        author: &PublicKey,
+        height: Height,
+        metainfo: &str,
+        tx_hash: &Hash
+    ) -> Timestamp {
         Timestamp::new(
-            &Timestamp::sign(data_hash, datetime),
+            &Signature::zero(),
             datetime.timestamp(),
-            data_hash
+            &algorithm.storage_key(digest),
+            height.0,
+            &PublicKey::zero(),
+            author,
+            metainfo,
+            tx_hash,
+            datetime.timestamp_subsec_nanos(),
+            algorithm.id(),
+            digest
+        )
+    }
+
+    /// Converts schema version 0 record into not yet attested `Timestamp`.
+    pub fn from_legacy(legacy: &LegacyTimestamp) -> Timestamp {
+        Timestamp::new(
+            &Signature::zero(),
+            legacy.timestamp(),
+            legacy.data_hash(),
+            0,
+            &PublicKey::zero(),
+            &PublicKey::zero(),
+            "",
+            &Hash::zero(),
+            0,
+            HashAlgorithm::Sha256.id(),
+            legacy.data_hash().as_ref()
+        )
+    }
+
+    /// Data signed by the validator
+    pub fn payload(&self) -> TimestampPayload {
+        TimestampPayload::new(
+            self.data_hash(),
+            self.timestamp(),
+            self.height(),
+            self.author(),
+            self.metainfo(),
+            self.tx_hash(),
+            self.nanos(),
+            self.algorithm(),
+            self.digest()
         )
     }
 
-    fn sign(data_hash: &Hash, datetime: &DateTime<Utc>) -> Hash {
-        let mut bytes: Vec<u8> = data_hash.into_bytes();
-        bytes.append(& mut datetime.timestamp().into_bytes());
-        hash(bytes.as_slice())
+    /// Returns copy of the `Timestamp` attested by `signer`.
+    pub fn attest(&self, signer: &PublicKey, signature: &Signature) -> Timestamp {
+        Timestamp::new(
+            signature,
+            self.timestamp(),
+            self.data_hash(),
+            self.height(),
+            signer,
+            self.author(),
+            self.metainfo(),
+            self.tx_hash(),
+            self.nanos(),
+            self.algorithm(),
+            self.digest()
+        )
+    }
+
+    /// Hash algorithm of the document `digest`
+    pub fn hash_algorithm(&self) -> HashAlgorithm {
+        HashAlgorithm::from_id(self.algorithm()).unwrap_or_default()
+    }
+
+    /// Consolidated time the timestamp was created at, with nanosecond precision
+    pub fn datetime(&self) -> DateTime<Utc> {
+        Utc.timestamp(self.timestamp(), self.nanos())
+    }
+
+    pub fn is_attested(&self) -> bool {
+        *self.signer() != PublicKey::zero()
+    }
+
+    /// Checks the validator signature of the `Timestamp`.
+    pub fn verify_signature(&self) -> bool {
+        self.is_attested()
+            && crypto::verify(self.signature(), &self.payload().into_bytes(), self.signer())
     }
 }
 
@@ -58,22 +202,158 @@ impl<T: AsRef<Snapshot>> TimestampingSchema<T> {
     }
 
     pub fn timestamps(&self) -> ProofMapIndex<&Snapshot, Hash, Timestamp> {
+        ProofMapIndex::new("timestamping_service.timestamps_v1", self.view.as_ref())
+    }
+
+    /// Schema version 0 timestamps which are not attested yet
+    ///
+    /// The records are moved to the timestamps table by `TxAttest`, at most
+    /// `MAX_ATTESTATIONS_PER_BLOCK` per block. Until then they have no `TimestampProof`
+    /// and are not listed by `timestamps`, `timestamps_by_time` and `author_timestamps`.
+    pub fn legacy_timestamps(&self) -> ProofMapIndex<&Snapshot, Hash, LegacyTimestamp> {
         ProofMapIndex::new("timestamping_service.timestamps", self.view.as_ref())
     }
 
+    /// Position in `timestamps_by_time` all timestamps before which are attested
+    pub fn attestation_cursor(&self) -> u64 {
+        Entry::new("timestamping_service.attestation_cursor", self.view.as_ref())
+            .get()
+            .unwrap_or(0)
+    }
+
+    /// Data hashes of timestamps in the order of creation
+    ///
+    /// Consolidated time of `exonum_time` never decreases, so the list is ordered
+    /// by `Timestamp::timestamp` as well. Migrated schema version 0 records precede
+    /// all of the indexed ones and aren't included.
+    pub fn timestamps_by_time(&self) -> ListIndex<&Snapshot, Hash> {
+        ListIndex::new("timestamping_service.timestamps_by_time", self.view.as_ref())
+    }
+
+    /// Position of the first timestamp made at or after `since` in `timestamps_by_time`
+    pub fn time_position(&self, since: i64) -> u64 {
+        let by_time = self.timestamps_by_time();
+        let timestamps = self.timestamps();
+        let (mut low, mut high) = (0, by_time.len());
+        while low < high {
+            let middle = low + (high - low) / 2;
+            let time = by_time
+                .get(middle)
+                .and_then(|data_hash| timestamps.get(&data_hash))
+                .map_or(i64::max_value(), |timestamp| timestamp.timestamp());
+            if time < since {
+                low = middle + 1;
+            } else {
+                high = middle;
+            }
+        }
+        low
+    }
+
+    /// Data hashes of timestamps made by `author` in the order of creation
+    pub fn author_timestamps(&self, author: &PublicKey) -> ListIndex<&Snapshot, Hash> {
+        ListIndex::new_in_family("timestamping_service.author_timestamps", author, self.view.as_ref())
+    }
+
+    /// Document id (data hash of the first version) by data hash of any version
+    pub fn document_ids(&self) -> MapIndex<&Snapshot, Hash, Hash> {
+        MapIndex::new("timestamping_service.document_ids", self.view.as_ref())
+    }
+
+    /// Data hashes of the document versions in the order of creation
+    pub fn document_versions(&self, document_id: &Hash) -> ListIndex<&Snapshot, Hash> {
+        ListIndex::new_in_family("timestamping_service.document_versions", document_id, self.view.as_ref())
+    }
+
+    /// Provides document id for `data_hash`, the document without versions is its own id.
+    pub fn document_id(&self, data_hash: &Hash) -> Hash {
+        self.document_ids().get(data_hash).unwrap_or(*data_hash)
+    }
+
+    /// Provides timestamp by `stamp`, schema version 0 records are converted on the fly.
+    ///
+    /// Use `timestamps` to check whether the timestamp can be proven by `TimestampProof`.
     pub fn timestamp(&self, stamp: &Hash) -> Option<Timestamp> {
-        self.timestamps().get(stamp)
+        self.timestamps()
+            .get(stamp)
+            .or_else(|| self.legacy_timestamps().get(stamp).map(|t| Timestamp::from_legacy(&t)))
+    }
+
+    /// Provides up to `limit` data hashes of timestamps to be attested except `skipped` ones.
+    ///
+    /// New timestamps go first in the order of creation, then schema version 0 records.
+    pub fn pending_attestations(&self, limit: usize, skipped: &HashSet<Hash>) -> Vec<Hash> {
+        let by_time = self.timestamps_by_time();
+        let timestamps = self.timestamps();
+        let mut hashes: Vec<Hash> = by_time
+            .iter_from(self.attestation_cursor())
+            .filter(|data_hash| !skipped.contains(data_hash))
+            .filter(|data_hash| timestamps.get(data_hash).map_or(false, |t| !t.is_attested()))
+            .take(limit)
+            .collect();
+        let rest = limit - hashes.len();
+        hashes.extend(
+            self.legacy_timestamps()
+                .keys()
+                .filter(|data_hash| !skipped.contains(data_hash))
+                .take(rest)
+        );
+        hashes
     }
 
     pub fn state_hash(&self) -> Vec<Hash> {
-        vec![self.timestamps().merkle_root()]
+        vec![
+            self.timestamps().merkle_root(),
+            self.legacy_timestamps().merkle_root()
+        ]
     }
 }
 
 impl<'a> TimestampingSchema<&'a mut Fork> {
-    /// Returns a mutable version of the wallets table.
+    /// Returns a mutable version of the timestamps table.
     pub fn timestamps_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, Timestamp> {
+        ProofMapIndex::new("timestamping_service.timestamps_v1", &mut self.view)
+    }
+
+    /// Returns a mutable version of the schema version 0 timestamps table.
+    pub fn legacy_timestamps_mut(&mut self) -> ProofMapIndex<&mut Fork, Hash, LegacyTimestamp> {
         ProofMapIndex::new("timestamping_service.timestamps", &mut self.view)
     }
-}
 
+    /// Returns a mutable version of the time-ordered timestamps list.
+    pub fn timestamps_by_time_mut(&mut self) -> ListIndex<&mut Fork, Hash> {
+        ListIndex::new("timestamping_service.timestamps_by_time", &mut self.view)
+    }
+
+    /// Returns a mutable version of the `author` timestamps list.
+    pub fn author_timestamps_mut(&mut self, author: &PublicKey) -> ListIndex<&mut Fork, Hash> {
+        ListIndex::new_in_family("timestamping_service.author_timestamps", author, &mut self.view)
+    }
+
+    /// Returns a mutable version of the document ids table.
+    pub fn document_ids_mut(&mut self) -> MapIndex<&mut Fork, Hash, Hash> {
+        MapIndex::new("timestamping_service.document_ids", &mut self.view)
+    }
+
+    /// Returns a mutable version of the document versions list.
+    pub fn document_versions_mut(&mut self, document_id: &Hash) -> ListIndex<&mut Fork, Hash> {
+        ListIndex::new_in_family("timestamping_service.document_versions", document_id, &mut self.view)
+    }
+
+    /// Moves the attestation cursor past the attested timestamps.
+    pub fn advance_attestation_cursor(&mut self) {
+        let mut cursor = self.attestation_cursor();
+        {
+            let by_time = self.timestamps_by_time();
+            let timestamps = self.timestamps();
+            while by_time
+                .get(cursor)
+                .and_then(|data_hash| timestamps.get(&data_hash))
+                .map_or(false, |timestamp| timestamp.is_attested())
+            {
+                cursor += 1;
+            }
+        }
+        Entry::new("timestamping_service.attestation_cursor", &mut self.view).set(cursor);
+    }
+}
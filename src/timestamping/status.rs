@@ -0,0 +1,156 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use std::thread;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::{Duration, Instant};
+
+use exonum::blockchain::{Blockchain, Schema, TransactionErrorType};
+use exonum::crypto::Hash;
+use exonum::helpers::Height;
+use exonum::storage::Snapshot;
+
+/// Interval between status checks while waiting for commit
+const POLL_INTERVAL_MS: u64 = 100;
+
+/// Maximum number of requests simultaneously waiting for commit
+///
+/// Each waiting request holds one of the API server threads, which are shared
+/// by the whole public API (8 per CPU core by default).
+pub const MAX_WAITERS: usize = 4;
+
+/// Execution status of the submitted transaction
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
+#[serde(tag = "status", rename_all = "snake_case")]
+pub enum TransactionStatus {
+    /// Transaction is not known to the node
+    Unknown,
+    /// Transaction is in the pool waiting for commit
+    Pending,
+    /// Transaction is committed and executed successfully
+    Committed {
+        height: Height,
+    },
+    /// Transaction is committed, but its execution failed
+    Failed {
+        height: Height,
+        /// `errors::Error` code, `None` in the case of panic
+        code: Option<u8>,
+        description: Option<String>,
+    },
+}
+
+impl TransactionStatus {
+    /// Provides status of the transaction with the given `tx_hash`.
+    pub fn new(snapshot: &Snapshot, tx_hash: &Hash) -> TransactionStatus {
+        let schema = Schema::new(snapshot);
+
+        let location = match schema.transactions_locations().get(tx_hash) {
+            Some(location) => location,
+            None => {
+                return if schema.transactions_pool().contains(tx_hash) {
+                    TransactionStatus::Pending
+                } else {
+                    TransactionStatus::Unknown
+                };
+            }
+        };
+
+        let height = location.block_height();
+        match schema.transaction_results().get(tx_hash) {
+            Some(Err(error)) => {
+                let code = match error.error_type() {
+                    TransactionErrorType::Code(code) => Some(code),
+                    TransactionErrorType::Panic => None,
+                };
+                TransactionStatus::Failed {
+                    height,
+                    code,
+                    description: error.description().map(str::to_owned),
+                }
+            }
+            _ => TransactionStatus::Committed { height },
+        }
+    }
+
+    /// Waits up to `timeout` until the transaction with the given `tx_hash` is committed.
+    ///
+    /// Transaction just sent to the node may be not known yet, so `Unknown` status
+    /// is treated the same way as `Pending` one.
+    pub fn wait_for_commit(
+        blockchain: &Blockchain,
+        tx_hash: &Hash,
+        timeout: Duration
+    ) -> TransactionStatus {
+        let started = Instant::now();
+        loop {
+            let status = TransactionStatus::new(&blockchain.snapshot(), tx_hash);
+            if status.is_final() || started.elapsed() >= timeout {
+                return status;
+            }
+            thread::sleep(Duration::from_millis(POLL_INTERVAL_MS));
+        }
+    }
+
+    /// Returns `true` in the case of transaction is committed.
+    pub fn is_final(&self) -> bool {
+        match *self {
+            TransactionStatus::Committed { .. } | TransactionStatus::Failed { .. } => true,
+            _ => false,
+        }
+    }
+}
+
+/// Counter of the requests waiting for commit shared by the API handlers
+#[derive(Clone, Default)]
+pub struct Waiters {
+    count: Arc<AtomicUsize>,
+}
+
+impl Waiters {
+    pub fn new() -> Waiters {
+        Waiters::default()
+    }
+
+    /// Registers new waiting request, `None` in the case of `MAX_WAITERS` is reached.
+    ///
+    /// The request is unregistered once the returned guard is dropped.
+    pub fn acquire(&self) -> Option<WaitGuard> {
+        let mut count = self.count.load(Ordering::SeqCst);
+        loop {
+            if count >= MAX_WAITERS {
+                return None;
+            }
+            let result = self.count
+                .compare_exchange(count, count + 1, Ordering::SeqCst, Ordering::SeqCst);
+            match result {
+                Ok(_) => return Some(WaitGuard { count: self.count.clone() }),
+                Err(actual) => count = actual,
+            }
+        }
+    }
+}
+
+/// Registration of the waiting request, see `Waiters::acquire`
+pub struct WaitGuard {
+    count: Arc<AtomicUsize>,
+}
+
+impl Drop for WaitGuard {
+    fn drop(&mut self) {
+        self.count.fetch_sub(1, Ordering::SeqCst);
+    }
+}
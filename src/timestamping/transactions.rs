@@ -13,9 +13,12 @@
 // limitations under the License.
 
 
-use exonum::crypto::{Hash, PublicKey};
+use exonum::crypto::{Hash, PublicKey, Signature};
 use timestamping::SERVICE_ID;
 
+/// Maximum length of `doc_metainfo` in bytes
+pub const MAX_METAINFO_LEN: usize = 256;
+
 transactions! {
     pub TimestampingTransactions {
         const SERVICE_ID = SERVICE_ID;
@@ -24,5 +27,45 @@ transactions! {
             pub_key: &PublicKey,
             doc_hash: &Hash,
         }
+
+        /// Timestamp request with document metainfo (file name, MIME type, external reference, etc.)
+        struct TxWithMetainfo {
+            pub_key: &PublicKey,
+            doc_hash: &Hash,
+            doc_metainfo: &str,
+        }
+
+        /// New version of the document timestamped as `prev_hash`
+        struct TxNewVersion {
+            pub_key: &PublicKey,
+            doc_hash: &Hash,
+            prev_hash: &Hash,
+            doc_metainfo: &str,
+        }
+
+        /// Root of the client-side `MerkleBatch` of `leaves_count` documents
+        struct TxBatch {
+            pub_key: &PublicKey,
+            root_hash: &Hash,
+            leaves_count: u64,
+        }
+
+        /// Validator attestation of the `Timestamp`
+        struct TxAttest {
+            /// validator service key
+            pub_key: &PublicKey,
+            /// data hash of the attested timestamp
+            data_hash: &Hash,
+            /// signature of the `TimestampPayload`
+            attestation: &Signature,
+        }
+
+        /// Timestamp request of the document `digest` made with the `HashAlgorithm` `algorithm`
+        struct TxDigest {
+            pub_key: &PublicKey,
+            algorithm: u8,
+            digest: &[u8],
+            doc_metainfo: &str,
+        }
     }
 }
\ No newline at end of file
@@ -0,0 +1,357 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+pub mod ots;
+
+use std::collections::HashSet;
+
+use exonum::blockchain::Blockchain;
+use exonum::crypto::{CryptoHash, Hash, PublicKey};
+use exonum::messages::Message;
+
+use timestamping::SERVICE_ID;
+use timestamping::schema::Timestamp;
+use timestamping::proof::{TimestampProof, TIMESTAMPS_TABLE_INDEX};
+use timestamping::merkle::MerklePath;
+
+/// Reasons of the `TimestampProof` verification failure
+#[derive(Debug, Fail)]
+pub enum VerificationError {
+    /// Proof from the timestamps table to the `Timestamp` is malformed.
+    #[fail(display = "Malformed timestamp proof: {}", _0)]
+    MalformedTimestampProof(String),
+
+    /// Proof doesn't contain timestamp for the requested data hash.
+    #[fail(display = "Timestamp for {:?} is not proven", _0)]
+    TimestampNotFound(Hash),
+
+    /// Proof from the `state_hash` to the timestamps table is malformed.
+    #[fail(display = "Malformed table proof: {}", _0)]
+    MalformedTableProof(String),
+
+    /// Table proof doesn't match the timestamps table root.
+    #[fail(display = "Timestamps table root doesn't match the table proof")]
+    TableRootMismatch,
+
+    /// Table proof doesn't match the block `state_hash`.
+    #[fail(display = "Block state_hash doesn't match the table proof")]
+    StateHashMismatch,
+
+    /// Precommit is made for another block.
+    #[fail(display = "Precommit of validator {} is made for another block", _0)]
+    PrecommitBlockMismatch(u16),
+
+    /// Precommit is made by the validator which isn't known to the verifier.
+    #[fail(display = "Unknown validator {}", _0)]
+    UnknownValidator(u16),
+
+    /// Precommit signature is invalid.
+    #[fail(display = "Invalid precommit signature of validator {}", _0)]
+    InvalidPrecommitSignature(u16),
+
+    /// Validator attestation of the `Timestamp` is invalid.
+    #[fail(display = "Invalid timestamp attestation")]
+    InvalidAttestation,
+
+    /// Document isn't included into the Merkle batch.
+    #[fail(display = "Document is not included into the Merkle batch")]
+    InvalidInclusionPath,
+
+    /// `.ots` proof is malformed or uses unsupported features.
+    #[fail(display = "Malformed OpenTimestamps proof: {}", _0)]
+    MalformedOts(String),
+
+    /// `.ots` proof operations don't lead to the hash of the proven `Timestamp`.
+    #[fail(display = "OpenTimestamps commitment doesn't match the timestamp")]
+    CommitmentMismatch,
+
+    /// `.ots` attestation refers to another block than the timestamp proof.
+    #[fail(display = "Attested block doesn't match the timestamp proof")]
+    AttestedBlockMismatch,
+
+    /// Block isn't approved by the majority of validators.
+    #[fail(display = "Not enough precommits: {} of {} required", got, required)]
+    NotEnoughPrecommits { got: usize, required: usize },
+}
+
+/// Checks `proof` of the timestamp for `data_hash` against the consensus keys of `validators`
+/// and returns the proven `Timestamp`.
+///
+/// The following checks are performed:
+///  - the `ProofMapIndex` path from the timestamps table root to the `Timestamp`;
+///  - the validator attestation of the `Timestamp`, if it's already attested;
+///  - the aggregation of the timestamps table root into the block `state_hash`;
+///  - the block hash referenced by the precommits;
+///  - the precommit signatures of the majority of validators.
+pub fn verify_timestamp_proof(
+    proof: &TimestampProof,
+    validators: &[PublicKey],
+    data_hash: &Hash,
+) -> Result<Timestamp, VerificationError> {
+    let checked_timestamp = proof.to_timestamp.clone().check().map_err(|e| {
+        VerificationError::MalformedTimestampProof(format!("{:?}", e))
+    })?;
+    let timestamp = checked_timestamp
+        .entries()
+        .into_iter()
+        .find(|&(key, _)| key == data_hash)
+        .map(|(_, timestamp)| timestamp.clone())
+        .ok_or_else(|| VerificationError::TimestampNotFound(*data_hash))?;
+    if timestamp.is_attested() && !timestamp.verify_signature() {
+        return Err(VerificationError::InvalidAttestation);
+    }
+
+    let checked_table = proof.to_table.clone().check().map_err(|e| {
+        VerificationError::MalformedTableProof(format!("{:?}", e))
+    })?;
+    let table_key = Blockchain::service_table_unique_key(SERVICE_ID, TIMESTAMPS_TABLE_INDEX);
+    let table_root = checked_table
+        .entries()
+        .into_iter()
+        .find(|&(key, _)| *key == table_key)
+        .map(|(_, root)| *root)
+        .ok_or(VerificationError::TableRootMismatch)?;
+    if table_root != checked_timestamp.merkle_root() {
+        return Err(VerificationError::TableRootMismatch);
+    }
+
+    let block = &proof.block_proof.block;
+    if checked_table.merkle_root() != *block.state_hash() {
+        return Err(VerificationError::StateHashMismatch);
+    }
+
+    let block_hash = block.hash();
+    let mut approved = HashSet::new();
+    for precommit in &proof.block_proof.precommits {
+        let validator = precommit.validator().0;
+        if *precommit.block_hash() != block_hash || precommit.height() != block.height() {
+            return Err(VerificationError::PrecommitBlockMismatch(validator));
+        }
+        let key = validators
+            .get(validator as usize)
+            .ok_or(VerificationError::UnknownValidator(validator))?;
+        if !precommit.verify_signature(key) {
+            return Err(VerificationError::InvalidPrecommitSignature(validator));
+        }
+        approved.insert(validator);
+    }
+
+    let required = validators.len() * 2 / 3 + 1;
+    if approved.len() < required {
+        return Err(VerificationError::NotEnoughPrecommits {
+            got: approved.len(),
+            required,
+        });
+    }
+
+    Ok(timestamp)
+}
+
+/// Checks `proof` of the Merkle batch timestamp and inclusion `path` of the `document` hash
+/// into the batch, returns the proven `Timestamp` of the batch root.
+pub fn verify_batch_timestamp_proof(
+    proof: &TimestampProof,
+    validators: &[PublicKey],
+    document: &Hash,
+    path: &MerklePath,
+) -> Result<Timestamp, VerificationError> {
+    let root_hash = path.root(document);
+    verify_timestamp_proof(proof, validators, &root_hash).map_err(|e| match e {
+        VerificationError::TimestampNotFound(_) => VerificationError::InvalidInclusionPath,
+        e => e,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::SystemTime;
+
+    use chrono::Utc;
+    use futures::sync::mpsc;
+    use exonum::blockchain::{Block, Blockchain, BlockProof, GenesisConfig, ValidatorKeys};
+    use exonum::crypto::{gen_keypair, hash, Hash, PublicKey, SecretKey};
+    use exonum::helpers::{Height, Round, ValidatorId};
+    use exonum::messages::Precommit;
+    use exonum::node::ApiSender;
+    use exonum::storage::MemoryDB;
+
+    use blockchain::events::Subscribers;
+    use timestamping::TimestampingService;
+    use timestamping::digest::HashAlgorithm;
+    use timestamping::proof::TimestampProof;
+    use timestamping::schema::{Timestamp, TimestampingSchema};
+    use super::{verify_timestamp_proof, VerificationError};
+
+    const VALIDATORS_COUNT: usize = 4;
+
+    fn precommit(validator: usize, block_hash: &Hash, secret_key: &SecretKey) -> Precommit {
+        Precommit::new(
+            ValidatorId(validator as u16),
+            Height(1),
+            Round::first(),
+            &hash(b"propose"),
+            block_hash,
+            SystemTime::now(),
+            secret_key,
+        )
+    }
+
+    /// Commits the block with the timestamp of `data_hash` precommitted by all validators,
+    /// returns the blockchain and the validators keys.
+    pub fn committed_blockchain(data_hash: &Hash) -> (Blockchain, Vec<(PublicKey, SecretKey)>) {
+        let validators: Vec<(PublicKey, SecretKey)> =
+            (0..VALIDATORS_COUNT).map(|_| gen_keypair()).collect();
+        let (service_key, service_secret) = gen_keypair();
+        let mut blockchain = Blockchain::new(
+            Box::new(MemoryDB::new()),
+            vec![Box::new(TimestampingService::new(Subscribers::new()))],
+            service_key,
+            service_secret,
+            ApiSender::new(mpsc::channel(1).0),
+        );
+        let genesis = GenesisConfig::new(validators.iter().map(|&(consensus_key, _)| {
+            ValidatorKeys {
+                consensus_key,
+                service_key: gen_keypair().0,
+            }
+        }));
+        blockchain.initialize(genesis).unwrap();
+
+        let mut fork = blockchain.fork();
+        {
+            let mut schema = TimestampingSchema::new(&mut fork);
+            let timestamp = Timestamp::from_parts(
+                &Utc::now(),
+                HashAlgorithm::Sha256,
+                data_hash.as_ref(),
+                &PublicKey::zero(),
+                Height(1),
+                "",
+                &Hash::zero()
+            );
+            schema.timestamps_mut().put(data_hash, timestamp);
+        }
+        blockchain.merge(fork.into_patch()).unwrap();
+
+        let (block_hash, patch) = blockchain.create_patch(ValidatorId::zero(), Height(1), &[]);
+        let precommits: Vec<Precommit> = validators
+            .iter()
+            .enumerate()
+            .map(|(validator, &(_, ref secret_key))| precommit(validator, &block_hash, secret_key))
+            .collect();
+        blockchain.commit(&patch, block_hash, precommits.iter()).unwrap();
+        (blockchain, validators)
+    }
+
+    /// Proof of the timestamp of `data_hash` committed by `committed_blockchain`
+    fn committed_proof(data_hash: &Hash) -> (TimestampProof, Vec<(PublicKey, SecretKey)>) {
+        let (blockchain, validators) = committed_blockchain(data_hash);
+        (TimestampProof::new(&blockchain.snapshot(), data_hash), validators)
+    }
+
+    pub fn consensus_keys(validators: &[(PublicKey, SecretKey)]) -> Vec<PublicKey> {
+        validators.iter().map(|&(public_key, _)| public_key).collect()
+    }
+
+    #[test]
+    fn test_valid_proof() {
+        let data_hash = hash(b"document");
+        let (proof, validators) = committed_proof(&data_hash);
+
+        let timestamp = verify_timestamp_proof(&proof, &consensus_keys(&validators), &data_hash)
+            .unwrap();
+        assert_eq!(*timestamp.data_hash(), data_hash);
+    }
+
+    #[test]
+    fn test_wrong_data_hash() {
+        let data_hash = hash(b"document");
+        let other_hash = hash(b"other document");
+        let (proof, validators) = committed_proof(&data_hash);
+
+        match verify_timestamp_proof(&proof, &consensus_keys(&validators), &other_hash) {
+            Err(VerificationError::TimestampNotFound(hash)) => assert_eq!(hash, other_hash),
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_tampered_state_hash() {
+        let data_hash = hash(b"document");
+        let (mut proof, validators) = committed_proof(&data_hash);
+        let block = proof.block_proof.block.clone();
+        proof.block_proof.block = Block::new(
+            block.proposer_id(),
+            block.height(),
+            block.tx_count(),
+            block.prev_hash(),
+            block.tx_hash(),
+            &hash(b"tampered state"),
+        );
+
+        match verify_timestamp_proof(&proof, &consensus_keys(&validators), &data_hash) {
+            Err(VerificationError::StateHashMismatch) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_precommit_for_another_block() {
+        let data_hash = hash(b"document");
+        let (mut proof, validators) = committed_proof(&data_hash);
+        proof.block_proof.precommits[1] = precommit(1, &hash(b"another block"), &validators[1].1);
+
+        match verify_timestamp_proof(&proof, &consensus_keys(&validators), &data_hash) {
+            Err(VerificationError::PrecommitBlockMismatch(1)) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_duplicated_precommit() {
+        let data_hash = hash(b"document");
+        let (proof, validators) = committed_proof(&data_hash);
+        let precommits = &proof.block_proof.precommits;
+        let proof = TimestampProof {
+            block_proof: BlockProof {
+                block: proof.block_proof.block.clone(),
+                precommits: vec![
+                    precommits[0].clone(),
+                    precommits[0].clone(),
+                    precommits[0].clone(),
+                    precommits[1].clone(),
+                ],
+            },
+            to_table: proof.to_table.clone(),
+            to_timestamp: proof.to_timestamp.clone(),
+        };
+
+        match verify_timestamp_proof(&proof, &consensus_keys(&validators), &data_hash) {
+            Err(VerificationError::NotEnoughPrecommits { got: 2, required: 3 }) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_not_enough_precommits() {
+        let data_hash = hash(b"document");
+        let (mut proof, validators) = committed_proof(&data_hash);
+        proof.block_proof.precommits.truncate(2);
+
+        match verify_timestamp_proof(&proof, &consensus_keys(&validators), &data_hash) {
+            Err(VerificationError::NotEnoughPrecommits { got: 2, required: 3 }) => {}
+            result => panic!("Unexpected result: {:?}", result),
+        }
+    }
+}
@@ -0,0 +1,204 @@
+// Copyright 2018 Ivan Penkin <grek.penkin@gmail.com>
+//
+// Licensed under the Apache License, Version 2.0 (the "License");
+// you may not use this file except in compliance with the License.
+// You may obtain a copy of the License at
+//
+//   http://www.apache.org/licenses/LICENSE-2.0
+//
+// Unless required by applicable law or agreed to in writing, software
+// distributed under the License is distributed on an "AS IS" BASIS,
+// WITHOUT WARRANTIES OR CONDITIONS OF ANY KIND, either express or implied.
+// See the License for the specific language governing permissions and
+// limitations under the License.
+
+
+use exonum::crypto::{CryptoHash, Hash, PublicKey};
+use serde_json;
+
+use timestamping::schema::Timestamp;
+use timestamping::ots::{ExonumAttestation, OtsOp, OtsProof, EXONUM_ATTESTATION_TAG,
+                        MAX_PAYLOAD_LEN, OP_APPEND, OP_PREPEND, OP_SHA256, OTS_MAGIC, OTS_VERSION,
+                        TAG_ATTESTATION, TAG_FORK};
+use super::{verify_timestamp_proof, VerificationError};
+
+/// Maximum length of the `append` / `prepend` operand
+const MAX_OPERAND_LEN: u64 = 4096;
+
+/// Sequential reader of the `.ots` file
+struct Reader<'a> {
+    data: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn read_bytes(&mut self, len: usize) -> Result<&'a [u8], VerificationError> {
+        if self.data.len() < len {
+            return Err(malformed("Unexpected end of data"));
+        }
+        let (bytes, rest) = self.data.split_at(len);
+        self.data = rest;
+        Ok(bytes)
+    }
+
+    fn read_u8(&mut self) -> Result<u8, VerificationError> {
+        self.read_bytes(1).map(|bytes| bytes[0])
+    }
+
+    fn read_varuint(&mut self) -> Result<u64, VerificationError> {
+        let mut value = 0u64;
+        let mut shift = 0;
+        loop {
+            let byte = self.read_u8()?;
+            if shift > 63 {
+                return Err(malformed("Too long varuint"));
+            }
+            value |= u64::from(byte & 0x7f) << shift;
+            if byte & 0x80 == 0 {
+                return Ok(value);
+            }
+            shift += 7;
+        }
+    }
+
+    fn read_varbytes(&mut self, max_len: u64) -> Result<&'a [u8], VerificationError> {
+        let len = self.read_varuint()?;
+        if len > max_len {
+            return Err(malformed("Too long operand"));
+        }
+        self.read_bytes(len as usize)
+    }
+
+    fn read_hash(&mut self) -> Result<Hash, VerificationError> {
+        let bytes = self.read_bytes(Hash::zero().as_ref().len())?;
+        Ok(Hash::from_slice(bytes).unwrap())
+    }
+}
+
+fn malformed(reason: &str) -> VerificationError {
+    VerificationError::MalformedOts(reason.to_owned())
+}
+
+/// Parses `.ots` file produced by `OtsProof::to_bytes`.
+///
+/// Only SHA-256 file hashes and linear proofs ending with `ExonumAttestation` are supported.
+pub fn parse_ots(bytes: &[u8]) -> Result<OtsProof, VerificationError> {
+    let mut reader = Reader { data: bytes };
+    if reader.read_bytes(OTS_MAGIC.len())? != OTS_MAGIC {
+        return Err(malformed("Not an OpenTimestamps proof"));
+    }
+    if reader.read_varuint()? != OTS_VERSION {
+        return Err(malformed("Unsupported version"));
+    }
+    if reader.read_u8()? != OP_SHA256 {
+        return Err(malformed("Unsupported file hash algorithm"));
+    }
+    let file_hash = reader.read_hash()?;
+
+    let mut ops = Vec::new();
+    loop {
+        match reader.read_u8()? {
+            OP_SHA256 => ops.push(OtsOp::Sha256),
+            OP_APPEND => ops.push(OtsOp::Append(reader.read_varbytes(MAX_OPERAND_LEN)?.to_vec())),
+            OP_PREPEND => ops.push(OtsOp::Prepend(reader.read_varbytes(MAX_OPERAND_LEN)?.to_vec())),
+            TAG_ATTESTATION => break,
+            TAG_FORK => return Err(malformed("Forked proofs are not supported")),
+            op => return Err(malformed(&format!("Unsupported operation {:#04x}", op))),
+        }
+    }
+
+    if reader.read_bytes(EXONUM_ATTESTATION_TAG.len())? != EXONUM_ATTESTATION_TAG {
+        return Err(malformed("Unsupported attestation"));
+    }
+    let mut payload = Reader { data: reader.read_varbytes(MAX_PAYLOAD_LEN as u64)? };
+    if !reader.data.is_empty() {
+        return Err(malformed("Trailing data"));
+    }
+
+    let height = payload.read_varuint()?;
+    let block_hash = payload.read_hash()?;
+    let proof = serde_json::from_slice(payload.data)
+        .map_err(|e| malformed(&format!("Invalid timestamp proof: {}", e)))?;
+
+    Ok(OtsProof {
+        file_hash,
+        ops,
+        attestation: ExonumAttestation {
+            height,
+            block_hash,
+            proof,
+        },
+    })
+}
+
+/// Checks the `.ots` proof of `data_hash` against the validators consensus keys.
+///
+/// The commitment must be the hash of the proven `Timestamp`, and the attested block
+/// must be the block of the timestamp proof.
+pub fn verify_ots_proof(
+    proof: &OtsProof,
+    validators: &[PublicKey],
+    data_hash: &Hash,
+) -> Result<Timestamp, VerificationError> {
+    if proof.file_hash != *data_hash {
+        return Err(VerificationError::TimestampNotFound(*data_hash));
+    }
+
+    let attestation = &proof.attestation;
+    let block = &attestation.proof.block_proof.block;
+    if block.hash() != attestation.block_hash || block.height().0 != attestation.height {
+        return Err(VerificationError::AttestedBlockMismatch);
+    }
+
+    let timestamp = verify_timestamp_proof(&attestation.proof, validators, data_hash)?;
+    if proof.commitment() != timestamp.hash().as_ref() {
+        return Err(VerificationError::CommitmentMismatch);
+    }
+    Ok(timestamp)
+}
+
+#[cfg(test)]
+mod tests {
+    use exonum::crypto::{hash, CryptoHash};
+
+    use timestamping::ots::{OtsError, OtsProof, MAX_PAYLOAD_LEN};
+    use super::super::tests::{committed_blockchain, consensus_keys};
+    use super::{parse_ots, verify_ots_proof};
+
+    #[test]
+    fn test_round_trip() {
+        let data_hash = hash(b"document");
+        let (blockchain, validators) = committed_blockchain(&data_hash);
+        let proof = OtsProof::new(&blockchain.snapshot(), &data_hash).unwrap();
+        assert!(proof.attestation.payload().len() <= MAX_PAYLOAD_LEN);
+
+        let parsed = parse_ots(&proof.to_bytes()).unwrap();
+        assert_eq!(parsed.file_hash, data_hash);
+        assert_eq!(parsed.ops, proof.ops);
+        assert_eq!(parsed.attestation.height, proof.attestation.height);
+        assert_eq!(parsed.attestation.block_hash, proof.attestation.block_hash);
+
+        let timestamp = verify_ots_proof(&parsed, &consensus_keys(&validators), &data_hash)
+            .unwrap();
+        assert_eq!(parsed.commitment(), timestamp.hash().as_ref());
+        assert_eq!(*timestamp.data_hash(), data_hash);
+    }
+
+    #[test]
+    fn test_unknown_timestamp() {
+        let (blockchain, _) = committed_blockchain(&hash(b"document"));
+        match OtsProof::new(&blockchain.snapshot(), &hash(b"other document")) {
+            Err(OtsError::TimestampNotFound) => {}
+            result => panic!("Unexpected result {:?}", result),
+        }
+    }
+
+    #[test]
+    fn test_truncated_proof() {
+        let data_hash = hash(b"document");
+        let (blockchain, _) = committed_blockchain(&data_hash);
+        let bytes = OtsProof::new(&blockchain.snapshot(), &data_hash).unwrap().to_bytes();
+        for len in 0..bytes.len() {
+            assert!(parse_ots(&bytes[..len]).is_err(), "truncated to {}", len);
+        }
+    }
+}
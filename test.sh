@@ -86,6 +86,22 @@ function check-timestamp {
     echo ""
 }
 
+function get-timestamp-proof {
+    RESP=`curl -H "Content-Type: application/json" -X GET $BASE_TS_URL/timestamp/$1/proof 2>/dev/null`
+}
+
+function check-timestamp-proof {
+    if [[ `echo $RESP | jq .block_proof.block.height` =~ ^\" ]]; then
+        echo "OK, got proof for timestamp $1"
+	echo "Response"
+	echo $RESP
+    else
+        echo "Unexpected response: $RESP"
+        STATUS=1
+    fi
+    echo ""
+}
+
 
 function get-height {
     RESP=`curl -H "Content-Type: application/json" -X GET $BASE_BC_URL/height 2>/dev/null`
@@ -106,6 +122,14 @@ function get-tx-hash {
 }
 
 
+function get-tx-status {
+    RESP=`curl -H "Content-Type: application/json" -X GET $BASE_TS_URL/timestamp/tx/$1/status 2>/dev/null`
+    echo "Transaction status: " $1
+    echo $RESP
+    echo ""
+}
+
+
 function get-tx {
     RESP=`curl -H "Content-Type: application/json" -X GET $BASE_BC_URL/transaction/$1 2>/dev/null`
     echo "Transaction: " $1
@@ -162,6 +186,10 @@ echo "Checking timestamp ef5725af0e304b092ad356083f94cd522a5d05b7360d7535dea1243
 get-timestamp ef5725af0e304b092ad356083f94cd522a5d05b7360d7535dea12436ee77d4c4
 check-timestamp ef5725af0e304b092ad356083f94cd522a5d05b7360d7535dea12436ee77d4c4
 
+echo "Checking timestamp proof b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60"
+get-timestamp-proof b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60
+check-timestamp-proof b32b3423e734f4ec4a9f73f934e314a87b49e48ab7fe97b7f84d606193571b60
+
 
 echo ""
 echo "Checking blockchain explorer API"
@@ -170,6 +198,7 @@ get-block $RESP
 
 get-tx-hash examples/timestaping-req-1.json
 get-tx $TX_HASH
+get-tx-status $TX_HASH
 
 get-tx-hash examples/timestaping-req-2.json
 get-tx $TX_HASH